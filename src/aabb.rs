@@ -9,11 +9,13 @@ pub struct AABB {
     pub z: Interval,
 }
 
-impl AABB {
-    pub fn default() -> Self {
+impl Default for AABB {
+    fn default() -> Self {
         Self { x: Interval::empty(), y: Interval::empty(), z: Interval::empty() }
     }
+}
 
+impl AABB {
     pub fn new(x: Interval, y: Interval, z: Interval) -> Self {
         Self { x, y, z }
     }
@@ -34,6 +36,12 @@ impl AABB {
         }
     }
 
+    // Widen any axis thinner than delta so flat shapes still have a box rays can enter.
+    pub fn pad(&self, delta: f64) -> Self {
+        let pad_axis = |axis: Interval| if axis.size() < delta { axis.expand(delta) } else { axis };
+        Self::new(pad_axis(self.x), pad_axis(self.y), pad_axis(self.z))
+    }
+
     pub fn axis(&self, index: usize) -> Interval {
         match index {
             0 => self.x,
@@ -0,0 +1,537 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::film::Film;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::integrator::Integrator;
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::spectrum::Wavelengths;
+use crate::vector::{Point3D, Vector3D};
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum VertexKind {
+    Camera,
+    Light,
+    Surface,
+}
+
+#[derive(Clone)]
+struct Vertex {
+    kind: VertexKind,
+    record: HitRecord,
+    beta: Color,
+    emission: Color,
+    pdf_forward: f64,
+    pdf_reverse: f64,
+    delta: bool,
+    adjoint: bool,
+}
+
+impl Vertex {
+    fn camera(point: Point3D, normal: Vector3D, beta: Color) -> Self {
+        Self {
+            kind: VertexKind::Camera,
+            record: HitRecord::new(point, normal, None, 0.0, 0.0, 0.0, true),
+            beta,
+            emission: Color::default(),
+            pdf_forward: 0.0,
+            pdf_reverse: 0.0,
+            delta: false,
+            adjoint: false,
+        }
+    }
+
+    fn light(record: HitRecord, emission: Color, pdf_origin: f64) -> Self {
+        Self {
+            kind: VertexKind::Light,
+            record,
+            beta: emission / pdf_origin,
+            emission,
+            pdf_forward: pdf_origin,
+            pdf_reverse: 0.0,
+            delta: false,
+            adjoint: false,
+        }
+    }
+
+    fn surface(record: HitRecord, material: &Arc<dyn Material>, beta: Color) -> Self {
+        Self {
+            kind: VertexKind::Surface,
+            emission: material.emitted(&record),
+            delta: material.is_specular(&record),
+            adjoint: false,
+            record,
+            beta,
+            pdf_forward: 0.0,
+            pdf_reverse: 0.0,
+        }
+    }
+
+    fn is_terminated(&self) -> bool { self.record.wavelengths.is_some_and(|wavelengths| wavelengths.is_terminated()) }
+
+    fn is_connectible(&self) -> bool {
+        match self.kind {
+            VertexKind::Camera | VertexKind::Light => true,
+            VertexKind::Surface => !self.delta,
+        }
+    }
+
+    fn cosine(&self, direction: &Vector3D) -> f64 {
+        if self.kind == VertexKind::Camera || self.record.medium { return 1.0; }
+        Vector3D::dot(&self.record.normal, &direction.normalized()).abs()
+    }
+
+    fn convert_density(&self, pdf: f64, next: &Vertex) -> f64 {
+        let offset = next.record.point - self.record.point;
+        let distance_squared = offset.length_squared();
+        if distance_squared == 0.0 { return 0.0; }
+
+        pdf * next.cosine(&offset) / distance_squared
+    }
+
+    fn f(&self, previous: &Vertex, next: &Vertex) -> Color {
+        let material = match &self.record.material {
+            Some(material) => material,
+            None => return Color::default(),
+        };
+
+        let direction_in = (self.record.point - previous.record.point).normalized();
+        let direction_out = (next.record.point - self.record.point).normalized();
+        let value = material.evaluate(&self.record, &direction_in, &direction_out);
+
+        if self.adjoint { value * material.adjoint_correction(&self.record, &direction_out) } else { value }
+    }
+
+    fn pdf(&self, camera: &Camera, previous: Option<&Vertex>, next: &Vertex) -> f64 {
+        let direction = (next.record.point - self.record.point).normalized();
+
+        let pdf = match self.kind {
+            VertexKind::Camera => camera.pdf_direction(&self.record.point, &direction),
+            VertexKind::Light => return self.pdf_light(next),
+            VertexKind::Surface => match (&self.record.material, previous) {
+                (Some(material), Some(previous)) => {
+                    material.scattering_pdf(&self.record, &(self.record.point - previous.record.point).normalized(), &direction)
+                }
+                _ => 0.0,
+            },
+        };
+
+        self.convert_density(pdf, next)
+    }
+
+    fn pdf_light(&self, next: &Vertex) -> f64 {
+        let direction = (next.record.point - self.record.point).normalized();
+        let pdf = Vector3D::dot(&self.record.normal, &direction).abs() / (2.0 * PI);
+
+        self.convert_density(pdf, next)
+    }
+}
+
+// Light subpaths start on the scene's sampleable emitters, gathered in prepare.
+#[derive(Default)]
+pub struct BidirectionalPathTracer {
+    lights: HittableList,
+}
+
+impl BidirectionalPathTracer {
+    fn pdf_light_origin(&self) -> f64 {
+        let area = self.lights.area();
+        if area > 0.0 { 1.0 / area } else { 0.0 }
+    }
+
+    fn transmittance(world: &dyn Hittable, from: &Point3D, to: &Point3D, time: f64, wavelengths: Option<Wavelengths>) -> Color {
+        let offset = *to - *from;
+        let distance = offset.length();
+
+        let ray = Ray::new(*from, offset / distance, time).with_wavelengths(wavelengths);
+        world.transmittance(&ray, &Interval::new(0.001, distance - 0.001))
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn random_walk(
+        ray: &Ray,
+        beta: Color,
+        pdf: f64,
+        max_vertices: usize,
+        path: &mut Vec<Vertex>,
+        camera: &Camera,
+        world: &dyn Hittable,
+        is_camera_path: bool,
+    ) -> Color {
+        let mut ray = *ray;
+        let mut beta = beta;
+        let mut pdf_forward = pdf;
+        let mut escaped = Color::default();
+
+        while path.len() < max_vertices {
+            let record = match world.hit(&ray, &mut Interval::new(0.001, f64::INFINITY)) {
+                Some(record) => record,
+                None => {
+                    if is_camera_path { escaped = beta * camera.background(&ray); }
+                    break;
+                }
+            };
+
+            let material = match record.material.clone() {
+                Some(material) => material,
+                None => break,
+            };
+
+            beta = beta * ray.transmittance(&record.point);
+            if material.is_specular(&record) && record.medium {
+                let mut attenuation = Color::default();
+                match material.scatter(&ray, &record, &mut attenuation) {
+                    Some(scattered) => {
+                        beta = beta * attenuation;
+                        ray = scattered.inherit(&ray);
+                        continue;
+                    }
+                    None => break,
+                }
+            }
+
+            let mut vertex = Vertex::surface(record.clone(), &material, beta);
+            vertex.adjoint = !is_camera_path;
+            vertex.pdf_forward = path[path.len() - 1].convert_density(pdf_forward, &vertex);
+            path.push(vertex);
+
+            if path.len() >= max_vertices { break; }
+
+            let mut attenuation = Color::default();
+            let scattered = match material.scatter(&ray, &record, &mut attenuation) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+
+            let direction_in = ray.direction().normalized();
+            let direction_out = scattered.direction().normalized();
+
+            let pdf_reverse = if material.is_specular(&record) {
+                pdf_forward = 0.0;
+                0.0
+            } else {
+                pdf_forward = material.scattering_pdf(&record, &direction_in, &direction_out);
+                material.scattering_pdf(&record, &-direction_out, &-direction_in)
+            };
+
+            beta = beta * attenuation;
+            if !is_camera_path { beta *= material.adjoint_correction(&record, &direction_out); }
+
+            let count = path.len();
+            path[count - 2].pdf_reverse = path[count - 1].convert_density(pdf_reverse, &path[count - 2]);
+
+            ray = Ray::new(scattered.origin(), direction_out, ray.time())
+                .with_wavelengths(scattered.wavelengths())
+                .with_absorption(scattered.absorption())
+                .inherit(&ray);
+        }
+
+        escaped
+    }
+
+    fn light_subpath(&self, max_vertices: usize, time: f64, wavelengths: Option<Wavelengths>, camera: &Camera, world: &dyn Hittable) -> Vec<Vertex> {
+        let mut path = Vec::with_capacity(max_vertices);
+        if max_vertices == 0 { return path; }
+
+        let record = match self.lights.sample_surface(time) {
+            Some(record) => HitRecord { wavelengths, ..record },
+            None => return path,
+        };
+
+        let emission = match &record.material {
+            Some(material) => material.emitted(&record),
+            None => return path,
+        };
+
+        let mut rng = rand::thread_rng();
+        let normal = if rng.gen_range(0.0..1.0) < 0.5 { record.normal } else { -record.normal };
+
+        let mut direction = normal + Vector3D::random_normal();
+        if direction.near_zero() { direction = normal; }
+        direction = direction.normalized();
+
+        let pdf_origin = self.pdf_light_origin();
+        let pdf_direction = Vector3D::dot(&normal, &direction) / (2.0 * PI);
+        if pdf_origin == 0.0 || pdf_direction <= 0.0 { return path; }
+
+        let ray = Ray::new(record.point, direction, time).with_wavelengths(wavelengths);
+        path.push(Vertex::light(record, emission, pdf_origin));
+
+        let beta = emission * Vector3D::dot(&normal, &direction) / (pdf_origin * pdf_direction);
+        Self::random_walk(&ray, beta, pdf_direction, max_vertices, &mut path, camera, world, false);
+
+        path
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn connect(
+        &self,
+        light_path: &[Vertex],
+        camera_path: &[Vertex],
+        s: usize,
+        t: usize,
+        time: f64,
+        wavelengths: Option<Wavelengths>,
+        camera: &Camera,
+        world: &dyn Hittable,
+        film: &Film,
+    ) -> Color {
+        let mut sampled: Option<Vertex> = None;
+        let mut raster: Option<(usize, usize)> = None;
+        let mut radiance = Color::default();
+
+        if s == 0 {
+            let pt = &camera_path[t - 1];
+            if pt.kind == VertexKind::Surface { radiance = pt.beta * pt.emission; }
+        } else if t == 1 {
+            let qs = &light_path[s - 1];
+            if !qs.is_connectible() || qs.kind != VertexKind::Surface { return Color::default(); }
+
+            let lens_point = camera.sample_lens();
+            let to_lens = lens_point - qs.record.point;
+            let distance_squared = to_lens.length_squared();
+            let direction = -to_lens.normalized();
+
+            raster = camera.raster_position(&lens_point, &direction);
+            if raster.is_none() { return Color::default(); }
+
+            let lens_cosine = Vector3D::dot(&direction, &camera.forward()).abs();
+            let pdf = distance_squared / (lens_cosine * camera.lens_area());
+            let camera_vertex = Vertex::camera(lens_point, camera.forward(), Color::new(1.0, 1.0, 1.0) * (camera.importance(&lens_point, &direction) / pdf));
+
+            radiance = qs.beta * qs.f(&light_path[s - 2], &camera_vertex) * camera_vertex.beta;
+            radiance *= qs.cosine(&direction);
+
+            if radiance.near_zero() { return Color::default(); }
+            radiance = radiance * Self::transmittance(world, &qs.record.point, &lens_point, time, wavelengths);
+            sampled = Some(camera_vertex);
+        } else if s == 1 {
+            let pt = &camera_path[t - 1];
+            if !pt.is_connectible() { return Color::default(); }
+
+            let record = match self.lights.sample_surface(time) {
+                Some(record) => HitRecord { wavelengths, ..record },
+                None => return Color::default(),
+            };
+
+            let emission = match &record.material {
+                Some(material) => material.emitted(&record),
+                None => return Color::default(),
+            };
+
+            let pdf_origin = self.pdf_light_origin();
+            let mut light_vertex = Vertex::light(record, emission, pdf_origin);
+
+            let to_light = light_vertex.record.point - pt.record.point;
+            let distance_squared = to_light.length_squared();
+            let direction = to_light.normalized();
+            let light_cosine = Vector3D::dot(&light_vertex.record.normal, &direction).abs();
+            if light_cosine == 0.0 || pdf_origin == 0.0 { return Color::default(); }
+
+            light_vertex.beta = emission * light_cosine / (pdf_origin * distance_squared);
+
+            radiance = pt.beta * pt.f(&camera_path[t - 2], &light_vertex) * light_vertex.beta;
+            radiance *= pt.cosine(&direction);
+
+            if radiance.near_zero() { return Color::default(); }
+            radiance = radiance * Self::transmittance(world, &pt.record.point, &light_vertex.record.point, time, wavelengths);
+            sampled = Some(light_vertex);
+        } else {
+            let qs = &light_path[s - 1];
+            let pt = &camera_path[t - 1];
+            if !qs.is_connectible() || !pt.is_connectible() { return Color::default(); }
+
+            let offset = pt.record.point - qs.record.point;
+            let distance_squared = offset.length_squared();
+            let direction = offset.normalized();
+            let geometry = qs.cosine(&direction) * pt.cosine(&direction) / distance_squared;
+
+            radiance = qs.beta * qs.f(&light_path[s - 2], pt) * pt.f(&camera_path[t - 2], qs) * pt.beta * geometry;
+
+            // Each subpath already scaled its hero wavelength up by the number it dropped.
+            if qs.is_terminated() && pt.is_terminated() { radiance /= 3.0; }
+
+            if radiance.near_zero() { return Color::default(); }
+            radiance = radiance * Self::transmittance(world, &qs.record.point, &pt.record.point, time, wavelengths);
+        }
+
+        if radiance.near_zero() { return Color::default(); }
+
+        let weight = self.mis_weight(light_path, camera_path, sampled.as_ref(), s, t, camera);
+        radiance *= weight;
+
+        if let Some((i, j)) = raster {
+            let radiance = match wavelengths {
+                Some(wavelengths) => wavelengths.to_rgb(&radiance),
+                None => radiance,
+            };
+
+            film.splat(i, j, &radiance);
+            return Color::default();
+        }
+
+        radiance
+    }
+
+    fn mis_weight(&self, light_path: &[Vertex], camera_path: &[Vertex], sampled: Option<&Vertex>, s: usize, t: usize, camera: &Camera) -> f64 {
+        if s + t == 2 || (s == 0 && (self.pdf_light_origin() == 0.0 || camera_path[t - 1].record.medium)) { return 1.0; }
+
+        let pt = if t == 1 { sampled.unwrap() } else { &camera_path[t - 1] };
+        let qs = if s == 1 { sampled } else if s > 1 { Some(&light_path[s - 1]) } else { None };
+        let pt_minus = if t > 1 { Some(&camera_path[t - 2]) } else { None };
+        let qs_minus = if s > 1 { Some(&light_path[s - 2]) } else { None };
+
+        let mut camera_pdfs: Vec<(f64, f64, bool)> = camera_path[..t].iter().map(|vertex| (vertex.pdf_forward, vertex.pdf_reverse, vertex.delta)).collect();
+        let mut light_pdfs: Vec<(f64, f64, bool)> = light_path[..s].iter().map(|vertex| (vertex.pdf_forward, vertex.pdf_reverse, vertex.delta)).collect();
+
+        camera_pdfs[t - 1] = (pt.pdf_forward, pt.pdf_reverse, false);
+        if let Some(qs) = qs { light_pdfs[s - 1] = (qs.pdf_forward, qs.pdf_reverse, false); }
+
+        camera_pdfs[t - 1].1 = match qs {
+            Some(qs) => qs.pdf(camera, qs_minus, pt),
+            None => self.pdf_light_origin(),
+        };
+
+        if let Some(pt_minus) = pt_minus {
+            camera_pdfs[t - 2].1 = match qs {
+                Some(qs) => pt.pdf(camera, Some(qs), pt_minus),
+                None => pt.pdf_light(pt_minus),
+            };
+        }
+
+        if let Some(qs) = qs { light_pdfs[s - 1].1 = pt.pdf(camera, pt_minus, qs); }
+        if let (Some(qs), Some(qs_minus)) = (qs, qs_minus) { light_pdfs[s - 2].1 = qs.pdf(camera, Some(pt), qs_minus); }
+
+        let remap = |pdf: f64| if pdf != 0.0 { pdf } else { 1.0 };
+        let mut sum = 0.0;
+
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            ratio *= remap(camera_pdfs[i].1) / remap(camera_pdfs[i].0);
+            if !camera_pdfs[i].2 && !camera_pdfs[i - 1].2 { sum += ratio; }
+        }
+
+        ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(light_pdfs[i].1) / remap(light_pdfs[i].0);
+            let delta_light = i > 0 && light_pdfs[i - 1].2;
+            if !light_pdfs[i].2 && !delta_light { sum += ratio; }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+}
+
+impl Integrator for BidirectionalPathTracer {
+    fn ray_color(&self, ray: &Ray, camera: &Camera, world: &dyn Hittable, film: &Film) -> Color {
+        let max_depth = camera.max_depth;
+        if max_depth == 0 { return Color::default(); }
+
+        let direction = ray.direction().normalized();
+        let camera_ray = Ray::new(ray.origin(), direction, ray.time()).with_wavelengths(ray.wavelengths());
+
+        let mut camera_path = Vec::with_capacity(max_depth + 1);
+        camera_path.push(Vertex::camera(ray.origin(), camera.forward(), Color::new(1.0, 1.0, 1.0)));
+
+        let pdf_direction = camera.pdf_direction(&ray.origin(), &direction);
+        let mut color = Self::random_walk(&camera_ray, Color::new(1.0, 1.0, 1.0), pdf_direction, max_depth + 1, &mut camera_path, camera, world, true);
+
+        let light_path = self.light_subpath(max_depth, ray.time(), ray.wavelengths(), camera, world);
+
+        for t in 1..=camera_path.len() {
+            for s in 0..=light_path.len() {
+                if (s == 1 && t == 1) || s + t < 2 || s + t - 1 > max_depth { continue; }
+                color += self.connect(&light_path, &camera_path, s, t, ray.time(), ray.wavelengths(), camera, world, film);
+            }
+        }
+
+        color
+    }
+
+    fn prepare(&mut self, world: &dyn Hittable) {
+        self.lights = HittableList::default();
+        world.collect_lights(&mut self.lights);
+    }
+
+    fn spectral(&self) -> bool { true }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::material::{DiffuseLight, Lambertian};
+    use crate::quad::Quad;
+
+    // Camera at the origin, two diffuse bounces and a point on a unit-area emitter, every vertex inside the view.
+    fn fixed_path() -> (BidirectionalPathTracer, Camera, [Vertex; 4], Vertex) {
+        let mut camera = Camera::new(1.0, 10, 1, 4, 90.0, Point3D::default(), Point3D::new(0.0, 0.0, -1.0), Vector3D::new(0.0, 1.0, 0.0), 0.0, 1.0);
+        camera.initialize();
+
+        let diffuse: Arc<dyn Material> = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let emitter: Arc<dyn Material> = Arc::new(DiffuseLight::new(Color::new(4.0, 4.0, 4.0)));
+
+        let mut tracer = BidirectionalPathTracer::default();
+        tracer.lights.add_object(Arc::new(Quad::new(Point3D::new(-0.3, 1.0, -2.3), Vector3D::new(1.0, 0.0, 0.0), Vector3D::new(0.0, 0.0, 1.0), emitter.clone())));
+
+        let surface = |point: Point3D, normal: Vector3D, material: &Arc<dyn Material>| {
+            Vertex::surface(HitRecord::new(point, normal.normalized(), Some(material.clone()), 0.0, 0.0, 0.0, true), material, Color::new(1.0, 1.0, 1.0))
+        };
+
+        let light_point = Point3D::new(0.2, 1.0, -1.8);
+        let light_record = HitRecord::new(light_point, Vector3D::new(0.0, -1.0, 0.0), Some(emitter.clone()), 0.0, 0.0, 0.0, true);
+        let path = [
+            Vertex::camera(Point3D::default(), camera.forward(), Color::new(1.0, 1.0, 1.0)),
+            surface(Point3D::new(0.1, 0.05, -2.0), Vector3D::new(0.0, 0.0, 1.0), &diffuse),
+            surface(Point3D::new(0.8, -0.6, -1.5), Vector3D::new(-0.5, 0.7, 0.5), &diffuse),
+            surface(light_point, Vector3D::new(0.0, -1.0, 0.0), &emitter),
+        ];
+        let light = Vertex::light(light_record, Color::new(4.0, 4.0, 4.0), tracer.pdf_light_origin());
+
+        (tracer, camera, path, light)
+    }
+
+    #[test]
+    fn strategy_weights_sum_to_one() {
+        let (tracer, camera, path, light) = fixed_path();
+        let [x0, x1, x2, x3] = &path;
+
+        // Densities of reaching each vertex from the camera end and from the light end of the path.
+        let from_camera = [0.0, x0.pdf(&camera, None, x1), x1.pdf(&camera, Some(x0), x2), x2.pdf(&camera, Some(x1), x3)];
+        let from_light = [x1.pdf(&camera, Some(x2), x0), x2.pdf(&camera, Some(x3), x1), light.pdf_light(x2), tracer.pdf_light_origin()];
+
+        let mut total = 0.0;
+        for t in 1..=path.len() {
+            let s = path.len() - t;
+
+            // Reverse densities next to the connection depend on it, so the subpaths leave placeholders there for mis_weight to fill in.
+            let unknown = |index: usize, length: usize| index + 2 >= length;
+            let camera_path: Vec<Vertex> = (0..t)
+                .map(|index| Vertex { pdf_forward: from_camera[index], pdf_reverse: if unknown(index, t) { 7.0 } else { from_light[index] }, ..path[index].clone() })
+                .collect();
+            let light_path: Vec<Vertex> = (0..s)
+                .map(|index| {
+                    let vertex = if index == 0 { light.clone() } else { path[3 - index].clone() };
+                    Vertex { pdf_forward: from_light[3 - index], pdf_reverse: if unknown(index, s) { 7.0 } else { from_camera[3 - index] }, ..vertex }
+                })
+                .collect();
+
+            let sampled = match (s, t) {
+                (1, _) => Some(light.clone()),
+                (_, 1) => Some(path[0].clone()),
+                _ => None,
+            };
+
+            let weight = tracer.mis_weight(&light_path, &camera_path, sampled.as_ref(), s, t, &camera);
+            assert!(weight > 0.0 && weight < 1.0, "strategy s = {s}, t = {t} has weight {weight}");
+            total += weight;
+        }
+
+        assert!((total - 1.0).abs() < 1e-9, "weights sum to {total}");
+    }
+}
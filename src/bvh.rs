@@ -1,23 +1,36 @@
+use std::cell::Cell;
 use std::cmp::Ordering;
 use std::sync::Arc;
 
 use rand::Rng;
 
 use crate::aabb::AABB;
+use crate::color::Color;
 use crate::hittable::{HitRecord, Hittable, HittableList};
 use crate::interval::Interval;
 use crate::ray::Ray;
 
+thread_local! {
+    static NODE_VISITS: Cell<usize> = const { Cell::new(0) };
+}
+
+pub fn reset_node_visits() { NODE_VISITS.with(|visits| visits.set(0)); }
+
+pub fn node_visits() -> usize { NODE_VISITS.with(|visits| visits.get()) }
+
 #[derive(Clone)]
 pub struct BVHNode {
     left: Arc<dyn Hittable>,
     right: Arc<dyn Hittable>,
+    // Media are kept out of the tree and tested once the surfaces have set the nearest hit.
+    media: Vec<Arc<dyn Hittable>>,
     bounding_box: AABB,
 }
 
 impl BVHNode {
-    pub fn new(objects: &Vec<Arc<dyn Hittable>>, start: usize, end: usize) -> Self {
-        let mut mut_objects = objects.clone();
+    pub fn new(objects: &[Arc<dyn Hittable>], start: usize, end: usize) -> Self {
+        // Copy only this node's range so building stays n log n for large scenes.
+        let (media, mut mut_objects): (Vec<_>, Vec<_>) = objects[start..end].iter().cloned().partition(|object| object.is_medium());
 
         let axis = rand::thread_rng().gen_range(0usize..=2usize);
         let comparator = match axis {
@@ -26,31 +39,35 @@ impl BVHNode {
             _ => BVHNode::box_z_compare
         };
 
-        let object_span = end - start;
+        let object_span = mut_objects.len();
 
-        let (left, right) = if object_span == 1 {
-            (mut_objects[start].clone(), mut_objects[start].clone())
+        let (left, right) = if object_span == 0 {
+            // An empty node hits nothing, so building from an empty list is safe.
+            let empty: Arc<dyn Hittable> = Arc::new(HittableList::default());
+            (empty.clone(), empty)
+        } else if object_span == 1 {
+            (mut_objects[0].clone(), mut_objects[0].clone())
         } else if object_span == 2 {
-            if comparator(mut_objects[start].clone(), mut_objects[start + 1].clone()) == Ordering::Less {
-                (mut_objects[start].clone(), mut_objects[start + 1].clone())
+            if comparator(mut_objects[0].clone(), mut_objects[1].clone()) == Ordering::Less {
+                (mut_objects[0].clone(), mut_objects[1].clone())
             } else {
-                (mut_objects[start + 1].clone(), mut_objects[start].clone())
+                (mut_objects[1].clone(), mut_objects[0].clone())
             }
         } else {
-            mut_objects[start..end].sort_by(|a, b| comparator(a.clone(), b.clone()));
+            mut_objects.sort_by(|a, b| comparator(a.clone(), b.clone()));
 
-            let middle = start + object_span / 2;
+            let middle = object_span / 2;
             (
-                Arc::new(BVHNode::new(&mut_objects, start, middle)) as Arc<dyn Hittable>,
-                Arc::new(BVHNode::new(&mut_objects, middle, end)) as Arc<dyn Hittable>
+                Arc::new(BVHNode::new(&mut_objects, 0, middle)) as Arc<dyn Hittable>,
+                Arc::new(BVHNode::new(&mut_objects, middle, object_span)) as Arc<dyn Hittable>
             )
         };
 
-        Self {
-            left: left.clone(),
-            right: right.clone(),
-            bounding_box: AABB::from_aabb_bounds(&left.clone().bounding_box(), &right.clone().bounding_box()),
-        }
+        let bounding_box = media.iter().fold(AABB::from_aabb_bounds(&left.bounding_box(), &right.bounding_box()), |bounds, medium| {
+            AABB::from_aabb_bounds(&bounds, &medium.bounding_box())
+        });
+
+        Self { left, right, media, bounding_box }
     }
 
     pub fn from_hittable_list(list: &HittableList) -> Self {
@@ -80,19 +97,38 @@ impl BVHNode {
 
 impl Hittable for BVHNode {
     fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
-        if !self.bounding_box.hit(ray, interval) { return None; }
+        NODE_VISITS.with(|visits| visits.set(visits.get() + 1));
+        // Test the box on a copy so a missed sibling cannot narrow the range the other child sees.
+        if !self.bounding_box.hit(ray, &mut interval.clone()) { return None; }
 
         let hit_left = self.left.hit(ray, interval);
-        let hit_right = self.right.hit(
-            ray,
-            &mut Interval::new(
-                interval.min,
-                if hit_left.clone().is_some() { hit_left.clone().unwrap().depth } else { interval.max }
-            )
-        );
+        let closest = hit_left.as_ref().map_or(interval.max, |record| record.depth);
+        let hit_right = self.right.hit(ray, &mut Interval::new(interval.min, closest));
+
+        let mut closest_hit = hit_right.or(hit_left);
+        for medium in &self.media {
+            let closest = closest_hit.as_ref().map_or(interval.max, |record| record.depth);
+            if let Some(record) = medium.hit(ray, &mut Interval::new(interval.min, closest)) { closest_hit = Some(record); }
+        }
+
+        closest_hit
+    }
+
+    fn transmittance(&self, ray: &Ray, interval: &Interval) -> Color {
+        if !self.bounding_box.hit(ray, &mut interval.clone()) { return Color::new(1.0, 1.0, 1.0); }
 
-        hit_left.or(hit_right)
+        let left = self.left.transmittance(ray, interval);
+        let surfaces = if Arc::ptr_eq(&self.left, &self.right) || left.near_zero() { left } else { left * self.right.transmittance(ray, interval) };
+
+        self.media.iter().fold(surfaces, |transmittance, medium| transmittance * medium.transmittance(ray, interval))
     }
 
     fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn is_medium(&self) -> bool { !self.media.is_empty() }
+
+    fn collect_lights(&self, lights: &mut HittableList) {
+        lights.add_lights(&self.left);
+        if !Arc::ptr_eq(&self.left, &self.right) { lights.add_lights(&self.right); }
+    }
 }
@@ -1,15 +1,16 @@
+use std::f64::consts::PI;
 use std::io;
 use std::io::Write;
-use std::sync::Arc;
 
 use rand::Rng;
 use rayon::prelude::*;
 
-use crate::color;
 use crate::color::Color;
+use crate::film::Film;
 use crate::hittable::Hittable;
-use crate::interval::Interval;
-use crate::ray::Ray;
+use crate::integrator::{Integrator, PathTracer};
+use crate::ray::{Ray, RayDifferential};
+use crate::spectrum::Wavelengths;
 use crate::vector::{Point3D, Vector3D};
 
 pub struct Camera {
@@ -26,6 +27,11 @@ pub struct Camera {
     pub defocus_angle: f64,
     pub focus_distance: f64,
 
+    pub integrator: Box<dyn Integrator>,
+    pub background: Option<Color>,
+    // Trace hero wavelengths instead of RGB when the integrator supports it; `--spectral` on the command line.
+    pub spectral: bool,
+
     image_height: usize,
     center: Point3D,
     pixel_location_100: Point3D,
@@ -40,8 +46,8 @@ pub struct Camera {
     defocus_disk_v: Vector3D,
 }
 
-impl Camera {
-    pub fn default() -> Self {
+impl Default for Camera {
+    fn default() -> Self {
         Self {
             aspect_ratio: 1.0,
             image_width: 100,
@@ -56,6 +62,10 @@ impl Camera {
             defocus_angle: 0.0,
             focus_distance: 10.0,
 
+            integrator: Box::new(PathTracer),
+            background: None,
+            spectral: false,
+
             image_height: usize::default(),
             center: Point3D::default(),
             pixel_location_100: Point3D::default(),
@@ -70,7 +80,10 @@ impl Camera {
             defocus_disk_v: Vector3D::default(),
         }
     }
+}
 
+impl Camera {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         aspect_ratio: f64,
         image_width: usize,
@@ -97,6 +110,10 @@ impl Camera {
             defocus_angle,
             focus_distance,
 
+            integrator: Box::new(PathTracer),
+            background: None,
+            spectral: false,
+
             image_height: usize::default(),
             center: Point3D::default(),
             pixel_location_100: Point3D::default(),
@@ -112,7 +129,7 @@ impl Camera {
         }
     }
 
-    fn initialize(&mut self) {
+    pub(crate) fn initialize(&mut self) {
         self.image_height = (self.image_width as f64 / self.aspect_ratio) as usize;
         self.center = self.look_from;
 
@@ -155,133 +172,157 @@ impl Camera {
         let pixel_center = self.pixel_location_100 + (self.pixel_delta_u * i as f64) + (self.pixel_delta_v * j as f64);
         let pixel_sample = pixel_center + self.pixel_sample_square();
 
-        let ray_origin = if self.defocus_angle <= 0.0 { self.center.clone() } else { self.defocus_disk_sample() };
+        let ray_origin = self.sample_lens();
         let ray_direction = pixel_sample - ray_origin;
         let ray_time = rand.gen_range(0.0..0.1);
 
-        Ray::new(ray_origin, ray_direction, ray_time)
+        let spacing = 1.0 / (self.samples_per_pixel.max(1) as f64).sqrt();
+        let differential = RayDifferential {
+            rx_origin: ray_origin,
+            rx_direction: ray_direction + self.pixel_delta_u * spacing,
+            ry_origin: ray_origin,
+            ry_direction: ray_direction + self.pixel_delta_v * spacing,
+        };
+
+        Ray::new(ray_origin, ray_direction, ray_time).with_differential(Some(differential))
     }
 
     pub fn render(&mut self, world: &dyn Hittable) {
         self.initialize();
 
-        println!("P3\n{} {}\n255\n", self.image_width, self.image_height);
+        let film = Film::new(self.image_width, self.image_height);
+        let passes = self.integrator.passes(self.samples_per_pixel).clamp(1, self.samples_per_pixel.max(1));
+        self.integrator.prepare(world);
 
-        for j in 0..self.image_height {
-            eprint!("\rLines Remaining: {}", self.image_height - j);
-            io::stderr().flush().unwrap();
+        for pass in 0..passes {
+            self.integrator.begin_pass(world, pass);
+            let samples = self.pass_samples(pass, passes);
 
-            for i in 0..self.image_width {
-                let mut pixel_color = Color::default();
+            for j in 0..self.image_height {
+                eprint!("\rPass {}/{}, Lines Remaining: {}", pass + 1, passes, self.image_height - j);
+                io::stderr().flush().unwrap();
 
-                for _ in 0..self.samples_per_pixel {
-                    let ray = self.get_ray(i, j);
-                    pixel_color += Camera::ray_color(&ray, self.max_depth, world);
-                }
+                for i in 0..self.image_width {
+                    let mut pixel_color = Color::default();
+
+                    for _ in 0..samples {
+                        pixel_color += self.sample(i, j, world, &film);
+                    }
 
-                color::write_color(&pixel_color, self.samples_per_pixel);
+                    film.add_sample(i, j, &pixel_color);
+                }
             }
         }
 
+        film.write(self.samples_per_pixel);
+
         eprintln!("\n\rDone.")
     }
 
-    pub fn render_parallel(&mut self, world: Arc<&dyn Hittable>) {
+    pub fn render_parallel(&mut self, world: &dyn Hittable) {
         self.initialize();
 
-        println!("P3\n{} {}\n255\n", self.image_width, self.image_height);
+        let film = Film::new(self.image_width, self.image_height);
+        let passes = self.integrator.passes(self.samples_per_pixel).clamp(1, self.samples_per_pixel.max(1));
+        self.integrator.prepare(world);
 
-        let lines: Vec<Vec<Color>> = (0..self.image_height).into_par_iter().map(|j| {
-            eprint!("\rCurrent Line: {j}");
-            io::stderr().flush().unwrap();
+        for pass in 0..passes {
+            self.integrator.begin_pass(world, pass);
+            let samples = self.pass_samples(pass, passes);
 
-            let pixel_colors: Vec<Color> = (0..self.image_width).into_par_iter().map(|i| {
-                let mut pixel_color = Color::default();
+            (0..self.image_height).into_par_iter().for_each(|j| {
+                eprint!("\rPass {}/{}, Current Line: {j}", pass + 1, passes);
+                io::stderr().flush().unwrap();
 
-                (0..self.samples_per_pixel).for_each(|_| {
-                    let ray = self.get_ray(i, j);
-                    pixel_color += Camera::ray_color_parallel(&ray, self.max_depth, world.clone());
-                });
-
-                pixel_color
-            }).collect();
+                (0..self.image_width).into_par_iter().for_each(|i| {
+                    let mut pixel_color = Color::default();
 
-            pixel_colors
-        }).collect();
+                    (0..samples).for_each(|_| {
+                        pixel_color += self.sample(i, j, world, &film);
+                    });
 
-        for line in lines {
-            for pixel_color in line {
-                color::write_color(&pixel_color, self.samples_per_pixel);
-            }
+                    film.add_sample(i, j, &pixel_color);
+                });
+            });
         }
 
+        film.write(self.samples_per_pixel);
+
         eprintln!("\n\rDone.")
     }
 
-    fn defocus_disk_sample(&self) -> Point3D {
-        let point = Vector3D::random_in_unit_disk();
-        self.center + self.defocus_disk_u * point.x() + self.defocus_disk_v * point.y()
+    // In spectral mode each camera ray carries its own wavelengths and its radiance is converted back to RGB here.
+    fn sample(&self, i: usize, j: usize, world: &dyn Hittable, film: &Film) -> Color {
+        let ray = self.get_ray(i, j);
+        if !self.spectral || !self.integrator.spectral() { return self.integrator.ray_color(&ray, self, world, film); }
+
+        let wavelengths = Wavelengths::sample();
+        let radiance = self.integrator.ray_color(&ray.with_wavelengths(Some(wavelengths)), self, world, film);
+
+        wavelengths.to_rgb(&radiance)
     }
 
-    fn ray_color(ray: &Ray, max_depth: usize, world: &dyn Hittable) -> Color {
-        if max_depth <= 0 { return Color::default(); }
-
-        if let Some(record) = world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
-            return match record.material {
-                None => Color::default(),
-
-                Some(material) => {
-                    let mut attenuation = Color::default();
-
-                    return if let Some(scattered) = material.scatter(
-                        ray,
-                        &record.point,
-                        &record.normal,
-                        record.front_face,
-                        &mut attenuation,
-                    ) {
-                        attenuation * Camera::ray_color(&scattered, max_depth - 1, world)
-                    } else {
-                        Color::default()
-                    };
-                }
-            };
+    fn pass_samples(&self, pass: usize, passes: usize) -> usize {
+        self.samples_per_pixel * (pass + 1) / passes - self.samples_per_pixel * pass / passes
+    }
+
+    pub fn background(&self, ray: &Ray) -> Color {
+        let background = self.background.unwrap_or_else(|| {
+            let direction_normal = ray.direction().normalized();
+            let a = (direction_normal.y() + 1.0) * 0.5;
+
+            Color::new(1.0, 1.0, 1.0) * (1.0 - a) + Color::new(0.5, 0.7, 1.0) * a
+        });
+
+        match ray.wavelengths() {
+            Some(wavelengths) => wavelengths.unbounded(&background),
+            None => background,
         }
+    }
 
-        let direction_normal = ray.direction().normalized();
-        let a = (direction_normal.y() + 1.0) * 0.5;
+    pub fn forward(&self) -> Vector3D { -self.w }
 
-        Color::new(1.0, 1.0, 1.0) * (1.0 - a) + Color::new(0.5, 0.7, 1.0) * a
+    pub fn sample_lens(&self) -> Point3D {
+        if self.defocus_angle <= 0.0 { self.center } else { self.defocus_disk_sample() }
     }
 
-    fn ray_color_parallel(ray: &Ray, max_depth: usize, world: Arc<&dyn Hittable>) -> Color {
-        if max_depth <= 0 { return Color::default(); }
-
-        if let Some(record) = world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
-            return match record.material {
-                None => Color::default(),
-
-                Some(material) => {
-                    let mut attenuation = Color::default();
-
-                    return if let Some(scattered) = material.scatter(
-                        ray,
-                        &record.point,
-                        &record.normal,
-                        record.front_face,
-                        &mut attenuation,
-                    ) {
-                        attenuation * Camera::ray_color_parallel(&scattered, max_depth - 1, world)
-                    } else {
-                        Color::default()
-                    };
-                }
-            };
-        }
+    pub fn lens_area(&self) -> f64 {
+        if self.defocus_angle <= 0.0 { 1.0 } else { PI * self.defocus_disk_u.length_squared() }
+    }
+
+    pub fn raster_position(&self, origin: &Point3D, direction: &Vector3D) -> Option<(usize, usize)> {
+        let direction = direction.normalized();
+        let cos_theta = Vector3D::dot(&direction, &self.forward());
+        if cos_theta <= 0.0 { return None; }
+
+        let focus_point = *origin + direction * (self.focus_distance / cos_theta);
+        let offset = focus_point - (self.pixel_location_100 - (self.pixel_delta_u + self.pixel_delta_v) * 0.5);
+
+        let x = Vector3D::dot(&offset, &self.pixel_delta_u) / self.pixel_delta_u.length_squared();
+        let y = Vector3D::dot(&offset, &self.pixel_delta_v) / self.pixel_delta_v.length_squared();
 
-        let direction_normal = ray.direction().normalized();
-        let a = (direction_normal.y() + 1.0) * 0.5;
+        if x < 0.0 || y < 0.0 || x >= self.image_width as f64 || y >= self.image_height as f64 { return None; }
 
-        Color::new(1.0, 1.0, 1.0) * (1.0 - a) + Color::new(0.5, 0.7, 1.0) * a
+        Some((x as usize, y as usize))
+    }
+
+    pub fn pdf_direction(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+        if self.raster_position(origin, direction).is_none() { return 0.0; }
+
+        let cos_theta = Vector3D::dot(&direction.normalized(), &self.forward());
+        let viewport_area = self.pixel_delta_u.length() * self.image_width as f64 * self.pixel_delta_v.length() * self.image_height as f64;
+        let unit_plane_area = viewport_area / self.focus_distance.powi(2);
+
+        1.0 / (unit_plane_area * cos_theta.powi(3))
+    }
+
+    pub fn importance(&self, origin: &Point3D, direction: &Vector3D) -> f64 {
+        let cos_theta = Vector3D::dot(&direction.normalized(), &self.forward());
+        self.pdf_direction(origin, direction) / (self.lens_area() * cos_theta)
+    }
+
+    fn defocus_disk_sample(&self) -> Point3D {
+        let point = Vector3D::random_in_unit_disk();
+        self.center + self.defocus_disk_u * point.x() + self.defocus_disk_v * point.y()
     }
 }
@@ -3,10 +3,38 @@ use crate::vector::Vector3D;
 
 pub type Color = Vector3D;
 
+const PLANCK: f64 = 6.626_070_15e-34;
+const BOLTZMANN: f64 = 1.380_649e-23;
+const LIGHT_SPEED: f64 = 299_792_458.0;
+
+pub(crate) fn planck(wavelength: f64, temperature: f64) -> f64 {
+    let numerator = 2.0 * PLANCK * LIGHT_SPEED.powi(2) / wavelength.powi(5);
+    numerator / ((PLANCK * LIGHT_SPEED / (wavelength * BOLTZMANN * temperature)).exp() - 1.0)
+}
+
+// Planck radiance sampled at red, green and blue wavelengths, normalised so a 6500K body has unit green.
+pub fn blackbody(temperature: f64) -> Color {
+    if temperature <= 0.0 { return Color::default(); }
+
+    let normalization = planck(550e-9, 6500.0);
+    Color::new(planck(610e-9, temperature), planck(550e-9, temperature), planck(465e-9, temperature)) / normalization
+}
+
+pub fn average(color: &Color) -> f64 { (color.x() + color.y() + color.z()) / 3.0 }
+
+// Beer-Lambert attenuation of each channel over a distance through coefficients sigma.
+pub fn transmittance(sigma: &Color, distance: f64) -> Color {
+    Color::new((-sigma.x() * distance).exp(), (-sigma.y() * distance).exp(), (-sigma.z() * distance).exp())
+}
+
 fn linear_to_gamma(linear_component: f64) -> f64 {
     linear_component.sqrt()
 }
 
+pub fn gamma_to_linear(gamma_component: f64) -> f64 {
+    gamma_component.powi(2)
+}
+
 pub fn write_color(pixel_color: &Color, samples_per_pixel: usize) {
     let scale = 1.0 / samples_per_pixel as f64;
 
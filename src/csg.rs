@@ -0,0 +1,162 @@
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::hittable::{HitRecord, Hittable, Span};
+use crate::interval::Interval;
+use crate::ray::Ray;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operation {
+    Union,
+    Intersection,
+    Difference,
+}
+
+impl Operation {
+    fn contains(&self, inside_left: bool, inside_right: bool) -> bool {
+        match self {
+            Operation::Union => inside_left || inside_right,
+            Operation::Intersection => inside_left && inside_right,
+            Operation::Difference => inside_left && !inside_right,
+        }
+    }
+}
+
+// Boolean combination of two closed solids. Each surface of the result keeps the material of the operand it came from.
+pub struct Csg {
+    left: Arc<dyn Hittable>,
+    right: Arc<dyn Hittable>,
+    operation: Operation,
+    bounding_box: AABB,
+}
+
+impl Csg {
+    pub fn new(operation: Operation, left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self {
+        let (left_box, right_box) = (left.bounding_box(), right.bounding_box());
+
+        let bounding_box = match operation {
+            Operation::Union => AABB::from_aabb_bounds(&left_box, &right_box),
+            Operation::Intersection => {
+                let overlap = |a: Interval, b: Interval| Interval::new(a.min.max(b.min), a.max.min(b.max));
+                AABB::new(overlap(left_box.x, right_box.x), overlap(left_box.y, right_box.y), overlap(left_box.z, right_box.z))
+            }
+            Operation::Difference => left_box,
+        };
+
+        Self { left, right, operation, bounding_box }
+    }
+
+    pub fn union(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self { Self::new(Operation::Union, left, right) }
+
+    pub fn intersection(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self { Self::new(Operation::Intersection, left, right) }
+
+    pub fn difference(left: Arc<dyn Hittable>, right: Arc<dyn Hittable>) -> Self { Self::new(Operation::Difference, left, right) }
+}
+
+impl Hittable for Csg {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        if !self.bounding_box.hit(ray, &mut interval.clone()) { return None; }
+
+        self.spans(ray)
+            .into_iter()
+            .flat_map(|span| [span.entry, span.exit])
+            .find(|record| interval.surrounds(record.depth))
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        if !self.bounding_box.hit(ray, &mut Interval::universe()) { return Vec::new(); }
+
+        // Sweep the operands' boundaries in order, tracking which solids the ray is inside.
+        let mut events: Vec<(bool, HitRecord)> = Vec::new();
+        for (is_left, operand) in [(true, &self.left), (false, &self.right)] {
+            for span in operand.spans(ray) {
+                events.push((is_left, span.entry));
+                events.push((is_left, span.exit));
+            }
+        }
+        events.sort_by(|a, b| a.1.depth.total_cmp(&b.1.depth));
+
+        let (mut inside_left, mut inside_right, mut inside) = (false, false, false);
+        let mut crossings = Vec::new();
+
+        for (is_left, mut record) in events {
+            if is_left { inside_left = record.front_face; } else { inside_right = record.front_face; }
+
+            let now_inside = self.operation.contains(inside_left, inside_right);
+            if now_inside == inside { continue; }
+            inside = now_inside;
+
+            // The stored normal already faces the ray, so only the side needs relabelling for surfaces that bound the result from the other side, such as a hole carved by the right operand.
+            record.front_face = now_inside;
+            crossings.push(record);
+        }
+
+        Span::from_crossings(crossings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+    use crate::sphere::Sphere;
+    use crate::vector::{Point3D, Vector3D};
+
+    // Unit spheres at x = 0 and x = 1, crossed along the x axis: the left solid spans [4, 6] and the right [5, 7].
+    fn spans(operation: Operation) -> Vec<(f64, f64)> {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let left = Arc::new(Sphere::new_static(Point3D::new(0.0, 0.0, 0.0), 1.0, material.clone()));
+        let right = Arc::new(Sphere::new_static(Point3D::new(1.0, 0.0, 0.0), 1.0, material));
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 0.0);
+
+        Csg::new(operation, left, right)
+            .spans(&ray)
+            .into_iter()
+            .map(|span| {
+                assert!(span.entry.front_face && !span.exit.front_face);
+                (span.entry.depth, span.exit.depth)
+            })
+            .collect()
+    }
+
+    fn assert_spans(actual: Vec<(f64, f64)>, expected: &[(f64, f64)]) {
+        assert_eq!(actual.len(), expected.len(), "spans {actual:?}, expected {expected:?}");
+        for (actual, expected) in actual.iter().zip(expected) {
+            assert!((actual.0 - expected.0).abs() < 1e-9 && (actual.1 - expected.1).abs() < 1e-9, "span {actual:?}, expected {expected:?}");
+        }
+    }
+
+    #[test]
+    fn union_merges_overlapping_spans() { assert_spans(spans(Operation::Union), &[(4.0, 7.0)]); }
+
+    #[test]
+    fn intersection_keeps_the_overlap() { assert_spans(spans(Operation::Intersection), &[(5.0, 6.0)]); }
+
+    #[test]
+    fn difference_stops_where_the_right_solid_begins() { assert_spans(spans(Operation::Difference), &[(4.0, 5.0)]); }
+
+    #[test]
+    fn difference_carves_a_hole_into_two_spans() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let outer = Arc::new(Sphere::new_static(Point3D::new(0.0, 0.0, 0.0), 2.0, material.clone()));
+        let inner = Arc::new(Sphere::new_static(Point3D::new(0.0, 0.0, 0.0), 1.0, material));
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 0.0);
+
+        let spans = Csg::difference(outer, inner).spans(&ray);
+        assert!(spans.iter().all(|span| span.entry.front_face && !span.exit.front_face));
+        assert_spans(spans.iter().map(|span| (span.entry.depth, span.exit.depth)).collect(), &[(3.0, 4.0), (6.0, 7.0)]);
+    }
+
+    #[test]
+    fn disjoint_intersection_is_empty() {
+        let material = Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5)));
+        let left = Arc::new(Sphere::new_static(Point3D::new(0.0, 0.0, 0.0), 1.0, material.clone()));
+        let right = Arc::new(Sphere::new_static(Point3D::new(3.0, 0.0, 0.0), 1.0, material));
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 0.0);
+
+        assert!(Csg::intersection(left, right).spans(&ray).is_empty());
+    }
+}
@@ -0,0 +1,307 @@
+use std::f64::consts::{FRAC_PI_2, SQRT_2};
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::bvh::BVHNode;
+use crate::hittable::{self, HitRecord, Hittable, HittableList};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{Point3D, Vector3D};
+
+const MAX_REFINEMENT: u32 = 10;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum CurveKind {
+    // Flat strip turned to face each ray, the usual choice for hair and fur.
+    Flat,
+    // Flat strip whose normal turns from the first to the second along the curve, such as a grass blade.
+    Ribbon([Vector3D; 2]),
+    // Ray-facing strip shaded as a round tube, for strands thick enough to show their sides.
+    Cylinder,
+}
+
+struct CurveCommon {
+    control_points: [Point3D; 4],
+    widths: [f64; 2],
+    kind: CurveKind,
+    texture_range: [f64; 2],
+    material: Arc<dyn Material>,
+    id: usize,
+}
+
+impl CurveCommon {
+    fn width(&self, u: f64) -> f64 { lerp(u, self.widths[0], self.widths[1]) }
+}
+
+// Cubic Bézier curve with a width varying linearly along it, or the piece of one covering part of its parameter range.
+#[derive(Clone)]
+pub struct Curve {
+    common: Arc<CurveCommon>,
+    u_range: [f64; 2],
+    bounding_box: AABB,
+}
+
+impl Curve {
+    pub fn bezier(control_points: [Point3D; 4], widths: [f64; 2], kind: CurveKind, material: Arc<dyn Material>) -> Self {
+        let common = CurveCommon { control_points, widths, kind, texture_range: [0.0, 1.0], material, id: hittable::next_object_id() };
+        Self::piece(Arc::new(common), [0.0, 1.0])
+    }
+
+    // Uniform cubic B-spline span, converted to the equivalent Bézier control points.
+    pub fn b_spline(control_points: [Point3D; 4], widths: [f64; 2], kind: CurveKind, material: Arc<dyn Material>) -> Self {
+        Self::bezier(b_spline_to_bezier(&control_points), widths, kind, material)
+    }
+
+    // Smooth strand through a polyline of guide points, one B-spline span per point with the ends clamped to the first and last.
+    // Width tapers from root to tip, u runs from 0 to 1 along the whole strand, and ribbon normals turn over its length.
+    pub fn strand(points: &[Point3D], widths: [f64; 2], kind: CurveKind, material: Arc<dyn Material>) -> Vec<Curve> {
+        let (Some(&first), Some(&last)) = (points.first(), points.last()) else { return Vec::new(); };
+        if points.len() < 2 { return Vec::new(); }
+
+        let padded: Vec<Point3D> = [first, first].into_iter().chain(points.iter().copied()).chain([last, last]).collect();
+        let spans = padded.len() - 3;
+        let id = hittable::next_object_id();
+
+        (0..spans)
+            .map(|i| {
+                let range = [i as f64 / spans as f64, (i + 1) as f64 / spans as f64];
+                let kind = match kind {
+                    CurveKind::Ribbon(normals) => CurveKind::Ribbon(range.map(|t| slerp(t, &normals[0], &normals[1]))),
+                    other => other,
+                };
+
+                let common = CurveCommon {
+                    control_points: b_spline_to_bezier(&[padded[i], padded[i + 1], padded[i + 2], padded[i + 3]]),
+                    widths: range.map(|t| lerp(t, widths[0], widths[1])),
+                    kind,
+                    texture_range: range,
+                    material: material.clone(),
+                    id,
+                };
+                Self::piece(Arc::new(common), [0.0, 1.0])
+            })
+            .collect()
+    }
+
+    // Cut into equal pieces along the parameter so each gets a tight box of its own in a BVH.
+    pub fn split(&self, pieces: usize) -> Vec<Curve> {
+        let pieces = pieces.max(1);
+        let [u0, u1] = self.u_range;
+
+        (0..pieces)
+            .map(|i| Self::piece(self.common.clone(), [lerp(i as f64 / pieces as f64, u0, u1), lerp((i + 1) as f64 / pieces as f64, u0, u1)]))
+            .collect()
+    }
+
+    fn piece(common: Arc<CurveCommon>, u_range: [f64; 2]) -> Self {
+        let points = segment(&common.control_points, u_range[0], u_range[1]);
+        let hull = points.iter().fold(AABB::from_vector_bounds(&points[0], &points[0]), |bounds, point| {
+            AABB::from_aabb_bounds(&bounds, &AABB::from_vector_bounds(point, point))
+        });
+
+        let width = common.width(u_range[0]).max(common.width(u_range[1]));
+        let bounding_box = AABB::new(hull.x.expand(width), hull.y.expand(width), hull.z.expand(width));
+
+        Self { common, u_range, bounding_box }
+    }
+
+    // Recursive subdivision in ray space, where the ray runs down the z axis from the origin, keeping the nearest hit.
+    fn refine(&self, points: &[Point3D; 4], u0: f64, u1: f64, depth: u32, direction: &Vector3D, range: &mut Interval) -> Option<CurveHit> {
+        let half_width = 0.5 * self.common.width(u0).max(self.common.width(u1));
+        let extent = |axis: usize| {
+            let values = points.map(|point| point[axis]);
+            (values.iter().cloned().fold(f64::INFINITY, f64::min) - half_width, values.iter().cloned().fold(f64::NEG_INFINITY, f64::max) + half_width)
+        };
+
+        let ((x_min, x_max), (y_min, y_max), (z_min, z_max)) = (extent(0), extent(1), extent(2));
+        if x_min > 0.0 || x_max < 0.0 || y_min > 0.0 || y_max < 0.0 || z_max < range.min || z_min > range.max { return None; }
+
+        if depth > 0 {
+            let halves = subdivide(points);
+            let middle = 0.5 * (u0 + u1);
+
+            let near = self.refine(&[halves[0], halves[1], halves[2], halves[3]], u0, middle, depth - 1, direction, range);
+            if let Some(hit) = &near { range.max = hit.distance; }
+            let far = self.refine(&[halves[3], halves[4], halves[5], halves[6]], middle, u1, depth - 1, direction, range);
+
+            return far.or(near);
+        }
+
+        // Only accept the ray between the perpendiculars at either end of the segment.
+        let start = (points[1].y() - points[0].y()) * -points[0].y() + points[0].x() * (points[0].x() - points[1].x());
+        let end = (points[2].y() - points[3].y()) * -points[3].y() + points[3].x() * (points[3].x() - points[2].x());
+        if start < 0.0 || end < 0.0 { return None; }
+
+        // Closest point to the ray along the chord, which the refinement depth keeps close to the curve.
+        let (chord_x, chord_y) = (points[3].x() - points[0].x(), points[3].y() - points[0].y());
+        let denominator = chord_x * chord_x + chord_y * chord_y;
+        if denominator == 0.0 { return None; }
+
+        let w = -(points[0].x() * chord_x + points[0].y() * chord_y) / denominator;
+        let u = lerp(w, u0, u1).clamp(u0, u1);
+
+        let mut width = self.common.width(u);
+        let ribbon_normal = match self.common.kind {
+            CurveKind::Ribbon(normals) => {
+                let normal = slerp(u, &normals[0], &normals[1]);
+                width *= Vector3D::dot(&normal, direction).abs();
+                Some(normal)
+            }
+            _ => None,
+        };
+
+        let (closest, tangent) = evaluate(points, w.clamp(0.0, 1.0));
+        let distance_squared = closest.x().powi(2) + closest.y().powi(2);
+        if distance_squared > 0.25 * width * width || !range.surrounds(closest.z()) { return None; }
+
+        // Which side of the centre line the ray passes decides whether v is above or below a half.
+        let offset = distance_squared.sqrt() / width;
+        let side = tangent.x() * -closest.y() + closest.x() * tangent.y();
+        let v = if side > 0.0 { 0.5 + offset } else { 0.5 - offset };
+
+        Some(CurveHit { distance: closest.z(), u, v, width, ribbon_normal })
+    }
+}
+
+struct CurveHit {
+    distance: f64,
+    u: f64,
+    v: f64,
+    width: f64,
+    ribbon_normal: Option<Vector3D>,
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        if !self.bounding_box.hit(ray, &mut interval.clone()) { return None; }
+
+        let length = ray.direction().length();
+        let direction = ray.direction() / length;
+        let points = segment(&self.common.control_points, self.u_range[0], self.u_range[1]);
+
+        let across = Vector3D::cross(&direction, &(points[3] - points[0]));
+        let frame = if across.near_zero() {
+            Onb::new(&direction)
+        } else {
+            let x = across.normalized();
+            Onb::from_axes(x, Vector3D::cross(&direction, &x), direction)
+        };
+        let local = points.map(|point| frame.to_local(&(point - ray.origin())));
+
+        // Subdivide until the pieces are flat to within a fraction of the width.
+        let flatness = (0..2)
+            .flat_map(|i| (0..3).map(move |axis| (i, axis)))
+            .map(|(i, axis)| (local[i][axis] - 2.0 * local[i + 1][axis] + local[i + 2][axis]).abs())
+            .fold(0.0, f64::max);
+        let epsilon = 0.05 * self.common.widths[0].max(self.common.widths[1]);
+        let depth = if flatness > 0.0 && epsilon > 0.0 { ((SQRT_2 * 6.0 * flatness / (8.0 * epsilon)).log2() / 2.0).clamp(0.0, MAX_REFINEMENT as f64) as u32 } else { 0 };
+
+        let mut range = Interval::new(interval.min * length, interval.max * length);
+        let hit = self.refine(&local, self.u_range[0], self.u_range[1], depth, &direction, &mut range)?;
+
+        let (_, dpdu) = evaluate(&self.common.control_points, hit.u);
+        let (normal, dpdv) = match hit.ribbon_normal {
+            Some(normal) => (normal, Vector3D::cross(&normal, &dpdu).normalized() * hit.width),
+            None => {
+                let side = Vector3D::cross(&direction, &dpdu);
+                let side = if side.near_zero() { frame.u() } else { side.normalized() };
+                let tangent = dpdu.normalized();
+                let facing = Vector3D::cross(&side, &tangent);
+
+                match self.common.kind {
+                    CurveKind::Cylinder => {
+                        // Bend the normal across the strip as a tube's would, from one edge through the front to the other.
+                        let (sine, cosine) = ((2.0 * hit.v - 1.0) * FRAC_PI_2).sin_cos();
+                        let normal = facing * cosine + side * sine;
+                        (normal, Vector3D::cross(&tangent, &normal) * hit.width)
+                    }
+                    _ => (-facing, side * hit.width),
+                }
+            }
+        };
+
+        let depth = hit.distance / length;
+        let [start, end] = self.common.texture_range;
+
+        let mut record = HitRecord { depth, time: ray.time(), point: ray.at(depth), ..HitRecord::default() };
+        record.set_face_normal(ray, &normal);
+        (record.u, record.v) = (lerp(hit.u, start, end), hit.v);
+        (record.dpdu, record.dpdv) = (dpdu / (end - start), dpdv);
+        record.object_id = self.common.id;
+        record.set_material(ray, self.common.material.clone());
+
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+}
+
+// Gather curves into one BVH, cutting each into pieces so long or bent curves are not tested as a single loose box.
+pub fn curve_bvh(curves: impl IntoIterator<Item = Curve>, pieces: usize) -> BVHNode {
+    let mut list = HittableList::default();
+    for curve in curves {
+        for piece in curve.split(pieces) { list.add_object(Arc::new(piece)); }
+    }
+
+    BVHNode::from_hittable_list(&list)
+}
+
+fn lerp<T>(t: f64, a: T, b: T) -> T
+where
+    T: std::ops::Mul<f64, Output = T> + std::ops::Add<Output = T>,
+{
+    a * (1.0 - t) + b * t
+}
+
+// Spherical interpolation between two directions, falling back to a normalised blend when they nearly coincide.
+fn slerp(t: f64, a: &Vector3D, b: &Vector3D) -> Vector3D {
+    let (a, b) = (a.normalized(), b.normalized());
+    let angle = Vector3D::dot(&a, &b).clamp(-1.0, 1.0).acos();
+    if angle.sin() < 1e-6 { return lerp(t, a, b).normalized(); }
+
+    (a * ((1.0 - t) * angle).sin() + b * (t * angle).sin()) / angle.sin()
+}
+
+fn b_spline_to_bezier(points: &[Point3D; 4]) -> [Point3D; 4] {
+    [
+        (points[0] + points[1] * 4.0 + points[2]) / 6.0,
+        (points[1] * 2.0 + points[2]) / 3.0,
+        (points[1] + points[2] * 2.0) / 3.0,
+        (points[1] + points[2] * 4.0 + points[3]) / 6.0,
+    ]
+}
+
+// Point and derivative by de Casteljau, using the chord when the end control points coincide.
+fn evaluate(points: &[Point3D; 4], u: f64) -> (Point3D, Vector3D) {
+    let first = [lerp(u, points[0], points[1]), lerp(u, points[1], points[2]), lerp(u, points[2], points[3])];
+    let second = [lerp(u, first[0], first[1]), lerp(u, first[1], first[2])];
+
+    let derivative = second[1] - second[0];
+    let derivative = if derivative.length_squared() > 0.0 { derivative * 3.0 } else { points[3] - points[0] };
+    (lerp(u, second[0], second[1]), derivative)
+}
+
+fn subdivide(points: &[Point3D; 4]) -> [Point3D; 7] {
+    [
+        points[0],
+        (points[0] + points[1]) / 2.0,
+        (points[0] + points[1] * 2.0 + points[2]) / 4.0,
+        (points[0] + points[1] * 3.0 + points[2] * 3.0 + points[3]) / 8.0,
+        (points[1] + points[2] * 2.0 + points[3]) / 4.0,
+        (points[2] + points[3]) / 2.0,
+        points[3],
+    ]
+}
+
+// Control points of the part of the curve between two parameters, from the curve's blossom.
+fn segment(points: &[Point3D; 4], u0: f64, u1: f64) -> [Point3D; 4] {
+    let blossom = |a: f64, b: f64, c: f64| {
+        let first = [lerp(a, points[0], points[1]), lerp(a, points[1], points[2]), lerp(a, points[2], points[3])];
+        let second = [lerp(b, first[0], first[1]), lerp(b, first[1], first[2])];
+        lerp(c, second[0], second[1])
+    };
+
+    [blossom(u0, u0, u0), blossom(u0, u0, u1), blossom(u0, u1, u1), blossom(u1, u1, u1)]
+}
@@ -0,0 +1,60 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::color;
+use crate::color::Color;
+
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<[AtomicU64; 3]>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Self { width, height, pixels: (0..width * height).map(|_| Default::default()).collect() }
+    }
+
+    pub fn width(&self) -> usize { self.width }
+
+    pub fn height(&self) -> usize { self.height }
+
+    pub fn add_sample(&self, i: usize, j: usize, color: &Color) {
+        let pixel = &self.pixels[j * self.width + i];
+
+        for (channel, value) in pixel.iter().enumerate() {
+            Self::atomic_add(value, color[channel]);
+        }
+    }
+
+    pub fn splat(&self, i: usize, j: usize, color: &Color) {
+        if i < self.width && j < self.height { self.add_sample(i, j, color); }
+    }
+
+    pub fn pixel(&self, i: usize, j: usize) -> Color {
+        let pixel = &self.pixels[j * self.width + i];
+
+        Color::new(
+            f64::from_bits(pixel[0].load(Ordering::Relaxed)),
+            f64::from_bits(pixel[1].load(Ordering::Relaxed)),
+            f64::from_bits(pixel[2].load(Ordering::Relaxed)),
+        )
+    }
+
+    pub fn write(&self, samples_per_pixel: usize) {
+        println!("P3\n{} {}\n255\n", self.width, self.height);
+
+        for j in 0..self.height {
+            for i in 0..self.width {
+                color::write_color(&self.pixel(i, j), samples_per_pixel);
+            }
+        }
+    }
+
+    fn atomic_add(value: &AtomicU64, delta: f64) {
+        if delta == 0.0 || !delta.is_finite() { return; }
+
+        let _ = value.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| {
+            Some((f64::from_bits(bits) + delta).to_bits())
+        });
+    }
+}
@@ -0,0 +1,349 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::vector::Point3D;
+
+const BRICK_SIZE: usize = 8;
+const BRICK_VOXELS: usize = BRICK_SIZE * BRICK_SIZE * BRICK_SIZE;
+const SPARSE_MAGIC: &[u8; 4] = b"HSVG";
+// A header can claim any resolution in a few bytes, so refuse grids whose brick table alone would exhaust memory.
+const MAX_BRICKS: usize = 1 << 24;
+
+pub struct VoxelGrid {
+    resolution: [usize; 3],
+    bricks: [usize; 3],
+    data: Vec<Option<Box<[f32; BRICK_VOXELS]>>>,
+}
+
+impl VoxelGrid {
+    pub fn new(resolution: [usize; 3]) -> Self {
+        let bricks = [0, 1, 2].map(|axis| resolution[axis].div_ceil(BRICK_SIZE).max(1));
+        Self { resolution, bricks, data: (0..bricks[0] * bricks[1] * bricks[2]).map(|_| None).collect() }
+    }
+
+    pub fn from_dense(resolution: [usize; 3], values: &[f32]) -> io::Result<Self> {
+        if values.len() < Self::voxel_count(resolution)? {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "fewer dense values than the grid resolution needs"));
+        }
+
+        Self::brick_count(resolution)?;
+        let mut grid = Self::new(resolution);
+
+        for z in 0..resolution[2] {
+            for y in 0..resolution[1] {
+                for x in 0..resolution[0] {
+                    let value = values[(z * resolution[1] + y) * resolution[0] + x];
+                    if value != 0.0 { grid.set(x, y, z, value); }
+                }
+            }
+        }
+
+        Ok(grid)
+    }
+
+    // Dense layout: three little-endian u32 dimensions followed by x-fastest little-endian f32 values.
+    pub fn load_dense<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        let resolution = Self::read_triple(&bytes, 0)?;
+
+        let count = Self::voxel_count(resolution)?;
+        let values = Self::read_f32s(&bytes, 12, count)?;
+
+        Self::from_dense(resolution, &values)
+    }
+
+    // Sparse layout: "HSVG", three u32 dimensions, a u32 entry count, then (u32 x, u32 y, u32 z, f32 value) entries.
+    pub fn load_sparse<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 20 || &bytes[0..4] != SPARSE_MAGIC {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "missing sparse voxel grid header"));
+        }
+
+        let resolution = Self::read_triple(&bytes, 4)?;
+        Self::brick_count(resolution)?;
+
+        let count = Self::read_u32s(&bytes, 16, 1)?[0] as usize;
+        let mut grid = Self::new(resolution);
+
+        for entry in 0..count {
+            let offset = 20 + entry * 16;
+            let index = Self::read_triple(&bytes, offset)?;
+            let value = Self::read_f32s(&bytes, offset + 12, 1)?[0];
+
+            let [x, y, z] = index;
+            if x >= resolution[0] || y >= resolution[1] || z >= resolution[2] {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "sparse voxel outside of grid"));
+            }
+
+            grid.set(x, y, z, value);
+        }
+
+        Ok(grid)
+    }
+
+    pub fn resolution(&self) -> [usize; 3] { self.resolution }
+
+    pub fn set(&mut self, x: usize, y: usize, z: usize, value: f32) {
+        let (brick, voxel) = self.locate(x, y, z);
+        self.data[brick].get_or_insert_with(|| Box::new([0.0; BRICK_VOXELS]))[voxel] = value;
+    }
+
+    pub fn value(&self, x: isize, y: isize, z: isize) -> f64 {
+        if x < 0 || y < 0 || z < 0 { return 0.0; }
+
+        let (x, y, z) = (x as usize, y as usize, z as usize);
+        if x >= self.resolution[0] || y >= self.resolution[1] || z >= self.resolution[2] { return 0.0; }
+
+        let (brick, voxel) = self.locate(x, y, z);
+        self.data[brick].as_ref().map_or(0.0, |values| values[voxel] as f64)
+    }
+
+    pub fn sample(&self, local: &Point3D) -> f64 {
+        let position = [0, 1, 2].map(|axis| local[axis] * self.resolution[axis] as f64 - 0.5);
+        let base = position.map(|value| value.floor());
+        let fraction = [0, 1, 2].map(|axis| position[axis] - base[axis]);
+        let [x, y, z] = base.map(|value| value as isize);
+
+        let mut result = 0.0;
+        for corner in 0..8 {
+            let (dx, dy, dz) = ((corner & 1) as isize, ((corner >> 1) & 1) as isize, ((corner >> 2) & 1) as isize);
+            let weight = (if dx == 1 { fraction[0] } else { 1.0 - fraction[0] })
+                * (if dy == 1 { fraction[1] } else { 1.0 - fraction[1] })
+                * (if dz == 1 { fraction[2] } else { 1.0 - fraction[2] });
+
+            if weight > 0.0 { result += weight * self.value(x + dx, y + dy, z + dz); }
+        }
+
+        result
+    }
+
+    pub fn majorants(&self) -> MajorantGrid {
+        let mut majorants = MajorantGrid { resolution: self.bricks, cell_size: [0.0; 3], values: vec![0.0; self.data.len()] };
+        majorants.cell_size = [0, 1, 2].map(|axis| BRICK_SIZE as f64 / self.resolution[axis].max(1) as f64);
+
+        for (brick, values) in self.data.iter().enumerate() {
+            let values = match values {
+                Some(values) => values,
+                None => continue,
+            };
+
+            let origin = [brick % self.bricks[0], (brick / self.bricks[0]) % self.bricks[1], brick / (self.bricks[0] * self.bricks[1])];
+
+            for (voxel, value) in values.iter().enumerate() {
+                if *value <= 0.0 { continue; }
+
+                let index = [voxel % BRICK_SIZE, (voxel / BRICK_SIZE) % BRICK_SIZE, voxel / (BRICK_SIZE * BRICK_SIZE)];
+                let global = [0, 1, 2].map(|axis| origin[axis] * BRICK_SIZE + index[axis]);
+
+                let low = [0, 1, 2].map(|axis| global[axis].saturating_sub(1) / BRICK_SIZE);
+                let high = [0, 1, 2].map(|axis| ((global[axis] + 1) / BRICK_SIZE).min(self.bricks[axis] - 1));
+
+                for z in low[2]..=high[2] {
+                    for y in low[1]..=high[1] {
+                        for x in low[0]..=high[0] {
+                            let cell = &mut majorants.values[(z * self.bricks[1] + y) * self.bricks[0] + x];
+                            *cell = cell.max(*value as f64);
+                        }
+                    }
+                }
+            }
+        }
+
+        majorants
+    }
+
+    fn locate(&self, x: usize, y: usize, z: usize) -> (usize, usize) {
+        let brick = ((z / BRICK_SIZE) * self.bricks[1] + y / BRICK_SIZE) * self.bricks[0] + x / BRICK_SIZE;
+        let voxel = ((z % BRICK_SIZE) * BRICK_SIZE + y % BRICK_SIZE) * BRICK_SIZE + x % BRICK_SIZE;
+
+        (brick, voxel)
+    }
+
+    fn voxel_count(resolution: [usize; 3]) -> io::Result<usize> {
+        resolution[0]
+            .checked_mul(resolution[1])
+            .and_then(|count| count.checked_mul(resolution[2]))
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "voxel grid resolution is too large"))
+    }
+
+    fn brick_count(resolution: [usize; 3]) -> io::Result<usize> {
+        Self::voxel_count(resolution)?;
+
+        resolution
+            .iter()
+            .try_fold(1usize, |count, axis| count.checked_mul(axis.div_ceil(BRICK_SIZE).max(1)))
+            .filter(|count| *count <= MAX_BRICKS)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "voxel grid has too many bricks"))
+    }
+
+    fn read_triple(bytes: &[u8], offset: usize) -> io::Result<[usize; 3]> {
+        let values = Self::read_u32s(bytes, offset, 3)?;
+        Ok([values[0] as usize, values[1] as usize, values[2] as usize])
+    }
+
+    fn read_u32s(bytes: &[u8], offset: usize, count: usize) -> io::Result<Vec<u32>> {
+        let end = count.checked_mul(4).and_then(|length| length.checked_add(offset));
+        let end = match end {
+            Some(end) if end <= bytes.len() => end,
+            _ => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "voxel grid file is truncated")),
+        };
+
+        Ok(bytes[offset..end].chunks_exact(4).map(|chunk| u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]])).collect())
+    }
+
+    fn read_f32s(bytes: &[u8], offset: usize, count: usize) -> io::Result<Vec<f32>> {
+        Ok(Self::read_u32s(bytes, offset, count)?.into_iter().map(f32::from_bits).collect())
+    }
+}
+
+pub struct MajorantGrid {
+    resolution: [usize; 3],
+    cell_size: [f64; 3],
+    values: Vec<f64>,
+}
+
+impl MajorantGrid {
+    pub fn traverse<F: FnMut(f64, f64, f64) -> bool>(&self, origin: &Point3D, direction: &Point3D, start: f64, end: f64, mut visit: F) {
+        let entry = *origin + *direction * start;
+
+        let mut cell = [0usize; 3];
+        let mut step = [0isize; 3];
+        let mut next = [f64::INFINITY; 3];
+        let mut delta = [f64::INFINITY; 3];
+
+        for axis in 0..3 {
+            let position = entry[axis] / self.cell_size[axis];
+            cell[axis] = (position.floor().max(0.0) as usize).min(self.resolution[axis] - 1);
+
+            if direction[axis] > 0.0 {
+                step[axis] = 1;
+                next[axis] = start + ((cell[axis] + 1) as f64 * self.cell_size[axis] - entry[axis]) / direction[axis];
+                delta[axis] = self.cell_size[axis] / direction[axis];
+            } else if direction[axis] < 0.0 {
+                step[axis] = -1;
+                next[axis] = start + (cell[axis] as f64 * self.cell_size[axis] - entry[axis]) / direction[axis];
+                delta[axis] = -self.cell_size[axis] / direction[axis];
+            }
+        }
+
+        let mut current = start;
+        while current < end {
+            let axis = if next[0] < next[1] && next[0] < next[2] { 0 } else if next[1] < next[2] { 1 } else { 2 };
+            let exit = next[axis].min(end);
+
+            let majorant = self.values[(cell[2] * self.resolution[1] + cell[1]) * self.resolution[0] + cell[0]];
+            if visit(current, exit, majorant) { return; }
+
+            current = exit;
+            let moved = cell[axis] as isize + step[axis];
+            if moved < 0 || moved >= self.resolution[axis] as isize { return; }
+
+            cell[axis] = moved as usize;
+            next[axis] += delta[axis];
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+
+    fn write(name: &str, bytes: &[u8]) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("halide-grid-{}-{name}", std::process::id()));
+        fs::write(&path, bytes).unwrap();
+        path
+    }
+
+    fn sparse(resolution: [u32; 3], entries: &[([u32; 3], f32)]) -> Vec<u8> {
+        let mut bytes = SPARSE_MAGIC.to_vec();
+        resolution.iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+        bytes.extend((entries.len() as u32).to_le_bytes());
+
+        for (index, value) in entries {
+            index.iter().for_each(|value| bytes.extend(value.to_le_bytes()));
+            bytes.extend(value.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    #[test]
+    fn sparse_entries_land_in_their_voxels() {
+        let grid = VoxelGrid::load_sparse(write("sparse", &sparse([10, 4, 3], &[([9, 3, 2], 0.5), ([0, 1, 0], 2.0)]))).unwrap();
+
+        assert_eq!(grid.resolution(), [10, 4, 3]);
+        assert_eq!(grid.value(9, 3, 2), 0.5);
+        assert_eq!(grid.value(0, 1, 0), 2.0);
+        assert_eq!(grid.value(1, 1, 0), 0.0);
+    }
+
+    #[test]
+    fn sparse_entry_outside_the_grid_is_rejected() {
+        let error = VoxelGrid::load_sparse(write("outside", &sparse([4, 4, 4], &[([1, 4, 0], 1.0)]))).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn truncated_files_are_rejected() {
+        let mut bytes = sparse([4, 4, 4], &[([1, 2, 3], 1.0), ([0, 0, 0], 1.0)]);
+        bytes.truncate(bytes.len() - 6);
+        assert_eq!(VoxelGrid::load_sparse(write("short-sparse", &bytes)).err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+
+        let mut bytes: Vec<u8> = [2u32, 2, 2].iter().flat_map(|value| value.to_le_bytes()).collect();
+        bytes.extend([1.0f32; 7].iter().flat_map(|value| value.to_le_bytes()));
+        assert_eq!(VoxelGrid::load_dense(write("short-dense", &bytes)).err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+
+        assert_eq!(VoxelGrid::load_dense(write("header", &[1, 0, 0, 0, 1])).err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    #[test]
+    fn oversized_headers_are_rejected_before_allocating() {
+        let error = VoxelGrid::load_sparse(write("bricks", &sparse([1 << 16, 1 << 16, 1 << 8], &[]))).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn short_dense_values_are_an_error() {
+        assert_eq!(VoxelGrid::from_dense([2, 2, 2], &[1.0; 7]).err().unwrap().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(VoxelGrid::from_dense([2, 2, 2], &[1.0; 8]).unwrap().value(1, 1, 1), 1.0);
+    }
+
+    #[test]
+    fn majorants_bound_the_density_along_every_segment() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut grid = VoxelGrid::new([21, 13, 9]);
+        for _ in 0..200 {
+            let (x, y, z) = (rng.gen_range(0..21), rng.gen_range(0..13), rng.gen_range(0..9));
+            grid.set(x, y, z, rng.gen_range(0.0..4.0));
+        }
+
+        let majorants = grid.majorants();
+        let mut point = || Point3D::new(rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+
+        for _ in 0..500 {
+            let (origin, target) = (point(), point());
+            let direction = target - origin;
+            let mut covered = 0.0;
+
+            majorants.traverse(&origin, &direction, 0.0, 1.0, |start, end, majorant| {
+                assert!((start - covered).abs() < 1e-9, "segments leave a gap at {covered}");
+                covered = end;
+
+                for step in 0..=16 {
+                    let depth = start + (end - start) * step as f64 / 16.0;
+                    let density = grid.sample(&(origin + direction * depth));
+                    assert!(density <= majorant + 1e-9, "density {density} above majorant {majorant} at depth {depth}");
+                }
+                false
+            });
+
+            assert!((covered - 1.0).abs() < 1e-9, "traversal stopped at {covered}");
+        }
+    }
+}
@@ -0,0 +1,299 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+
+use crate::color::{self, Color};
+use crate::hittable::HitRecord;
+use crate::material::{self, Material};
+use crate::microfacet;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::Vector3D;
+
+// Lobes for reflection, transmission, one internal bounce and everything after it.
+const LOBES: usize = 4;
+const REFRACTIVE_INDEX: f64 = 1.55;
+
+// Absorption coefficients of the two melanin pigments per unit concentration.
+const EUMELANIN: [f64; 3] = [0.419, 0.697, 1.37];
+const PHEOMELANIN: [f64; 3] = [0.187, 0.4, 1.05];
+
+// Fiber scattering after d'Eon and Chiang: longitudinal lobes from a von Mises-Fisher style term, azimuthal lobes from trimmed
+// logistics around the ideal refraction paths, and cuticle scales tilting each lobe. Meant for curves, which report the offset
+// across the fiber in v and the fiber direction in dpdu.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Hair {
+    sigma_a: Color,
+    longitudinal_roughness: f64,
+    azimuthal_roughness: f64,
+    scale_angle: f64,
+    id: usize,
+}
+
+impl Hair {
+    // Absorption per unit fiber diameter; roughnesses in (0, 1], scale tilt in degrees (around 2 for human hair).
+    pub fn new(sigma_a: Color, longitudinal_roughness: f64, azimuthal_roughness: f64, scale_angle: f64) -> Self {
+        Self {
+            sigma_a,
+            longitudinal_roughness: longitudinal_roughness.clamp(1e-3, 1.0),
+            azimuthal_roughness: azimuthal_roughness.clamp(1e-3, 1.0),
+            scale_angle,
+            id: material::next_material_id(),
+        }
+    }
+
+    // Natural hair colors, from blond near 0.3 to black around 8 for eumelanin, with pheomelanin adding red.
+    pub fn from_melanin(eumelanin: f64, pheomelanin: f64, longitudinal_roughness: f64, azimuthal_roughness: f64, scale_angle: f64) -> Self {
+        let [e, p] = [EUMELANIN, PHEOMELANIN].map(|[r, g, b]| Color::new(r, g, b));
+        Self::new(e * eumelanin + p * pheomelanin, longitudinal_roughness, azimuthal_roughness, scale_angle)
+    }
+
+    // Absorption giving roughly the requested multiple-scattering color, fitted by Chiang et al.
+    pub fn from_color(color: Color, longitudinal_roughness: f64, azimuthal_roughness: f64, scale_angle: f64) -> Self {
+        let b = azimuthal_roughness.clamp(1e-3, 1.0);
+        let denominator = 5.969 - 0.215 * b + 2.532 * b.powi(2) - 10.73 * b.powi(3) + 5.574 * b.powi(4) + 0.245 * b.powi(5);
+        let channel = |c: f64| (c.clamp(1e-4, 1.0).ln() / denominator).powi(2);
+
+        Self::new(Color::new(channel(color.x()), channel(color.y()), channel(color.z())), longitudinal_roughness, azimuthal_roughness, scale_angle)
+    }
+
+    fn variances(&self) -> [f64; LOBES] {
+        let b = self.longitudinal_roughness;
+        let v = (0.726 * b + 0.812 * b.powi(2) + 3.7 * b.powi(20)).powi(2);
+        [v, 0.25 * v, 4.0 * v, 4.0 * v]
+    }
+
+    fn logistic_scale(&self) -> f64 {
+        let b = self.azimuthal_roughness;
+        (PI / 8.0).sqrt() * (0.265 * b + 1.194 * b.powi(2) + 5.372 * b.powi(22))
+    }
+
+    fn sigma_a(&self, record: &HitRecord) -> Color {
+        match &record.wavelengths {
+            Some(wavelengths) => wavelengths.unbounded(&self.sigma_a),
+            None => self.sigma_a,
+        }
+    }
+
+    // Longitudinal angle of the outgoing direction shifted by the cuticle tilt for lobe p.
+    fn tilted(&self, p: usize, sin_theta: f64, cos_theta: f64) -> (f64, f64) {
+        let shift = match p {
+            0 => -2.0,
+            1 => 1.0,
+            2 => 4.0,
+            _ => return (sin_theta, cos_theta),
+        } * self.scale_angle.to_radians();
+
+        let (sin_shift, cos_shift) = shift.sin_cos();
+        (sin_theta * cos_shift + cos_theta * sin_shift, (cos_theta * cos_shift - sin_theta * sin_shift).abs())
+    }
+
+    fn geometry(&self, record: &HitRecord, wo: &Vector3D) -> Fiber {
+        let h = (2.0 * record.v - 1.0).clamp(-1.0 + 1e-6, 1.0 - 1e-6);
+        let sin_theta_o = wo.x();
+        let cos_theta_o = safe_sqrt(1.0 - sin_theta_o.powi(2));
+
+        let sin_theta_t = sin_theta_o / REFRACTIVE_INDEX;
+        let cos_theta_t = safe_sqrt(1.0 - sin_theta_t.powi(2));
+        let eta_perpendicular = (REFRACTIVE_INDEX.powi(2) - sin_theta_o.powi(2)).sqrt() / cos_theta_o.max(1e-9);
+        let sin_gamma_t = (h / eta_perpendicular).clamp(-1.0, 1.0);
+        let cos_gamma_t = safe_sqrt(1.0 - sin_gamma_t.powi(2));
+
+        let sigma_a = self.sigma_a(record);
+        let path = 2.0 * cos_gamma_t / cos_theta_t.max(1e-9);
+        let transmittance = color::transmittance(&sigma_a, path);
+
+        // Attenuation of each lobe: Fresnel at the entry, then alternating absorption and internal reflection.
+        let cos_gamma_o = safe_sqrt(1.0 - h.powi(2));
+        let fresnel = microfacet::fresnel_dielectric(cos_theta_o * cos_gamma_o, REFRACTIVE_INDEX);
+        let first = Color::new(1.0, 1.0, 1.0) * fresnel;
+        let second = transmittance * (1.0 - fresnel).powi(2);
+        let third = second * transmittance * fresnel;
+        let remainder = transmittance * fresnel;
+        let rest = Color::new(
+            third.x() * remainder.x() / (1.0 - remainder.x()),
+            third.y() * remainder.y() / (1.0 - remainder.y()),
+            third.z() * remainder.z() / (1.0 - remainder.z()),
+        );
+
+        Fiber {
+            sin_theta_o,
+            cos_theta_o,
+            phi_o: wo.z().atan2(wo.y()),
+            gamma_o: h.asin(),
+            gamma_t: sin_gamma_t.asin(),
+            attenuation: [first, second, third, rest],
+        }
+    }
+
+    fn lobe_weights(fiber: &Fiber) -> [f64; LOBES] {
+        let luminance = fiber.attenuation.map(|a| 0.2126 * a.x() + 0.7152 * a.y() + 0.0722 * a.z());
+        let total: f64 = luminance.iter().sum();
+        if total <= 0.0 { return [1.0, 0.0, 0.0, 0.0]; }
+        luminance.map(|l| l / total)
+    }
+
+    fn evaluate_local(&self, fiber: &Fiber, wi: &Vector3D) -> Color {
+        let (sin_theta_i, cos_theta_i) = (wi.x(), safe_sqrt(1.0 - wi.x().powi(2)));
+        let phi = wi.z().atan2(wi.y()) - fiber.phi_o;
+        let variances = self.variances();
+        let s = self.logistic_scale();
+
+        (0..LOBES).fold(Color::default(), |sum, p| {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, fiber.sin_theta_o, fiber.cos_theta_o);
+            let longitudinal = longitudinal(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, variances[p]);
+            sum + fiber.attenuation[p] * (longitudinal * fiber.azimuthal(p, phi, s))
+        })
+    }
+
+    fn pdf_local(&self, fiber: &Fiber, wi: &Vector3D) -> f64 {
+        let (sin_theta_i, cos_theta_i) = (wi.x(), safe_sqrt(1.0 - wi.x().powi(2)));
+        let phi = wi.z().atan2(wi.y()) - fiber.phi_o;
+        let variances = self.variances();
+        let s = self.logistic_scale();
+
+        Self::lobe_weights(fiber).iter().enumerate().map(|(p, weight)| {
+            let (sin_theta_op, cos_theta_op) = self.tilted(p, fiber.sin_theta_o, fiber.cos_theta_o);
+            weight * longitudinal(cos_theta_i, cos_theta_op, sin_theta_i, sin_theta_op, variances[p]) * fiber.azimuthal(p, phi, s)
+        }).sum()
+    }
+
+    fn sample_local(&self, fiber: &Fiber) -> Vector3D {
+        let mut rng = rand::thread_rng();
+        let weights = Self::lobe_weights(fiber);
+
+        let mut choice = rng.gen::<f64>();
+        let p = (0..LOBES - 1).find(|&p| { choice -= weights[p]; choice < 0.0 }).unwrap_or(LOBES - 1);
+
+        // Longitudinal angle around the tilted mirror direction.
+        let variance = self.variances()[p];
+        let (sin_theta_op, cos_theta_op) = self.tilted(p, fiber.sin_theta_o, fiber.cos_theta_o);
+        let u = rng.gen::<f64>().max(1e-5);
+        let cos_theta = 1.0 + variance * (u + (1.0 - u) * (-2.0 / variance).exp()).ln();
+        let sin_theta = safe_sqrt(1.0 - cos_theta.powi(2));
+        let cos_phi = (2.0 * PI * rng.gen::<f64>()).cos();
+        let sin_theta_i = (-cos_theta * sin_theta_op + sin_theta * cos_phi * cos_theta_op).clamp(-1.0, 1.0);
+        let cos_theta_i = safe_sqrt(1.0 - sin_theta_i.powi(2));
+
+        let delta_phi = if p < LOBES - 1 {
+            fiber.lobe_phi(p) + sample_trimmed_logistic(rng.gen(), self.logistic_scale())
+        } else {
+            2.0 * PI * rng.gen::<f64>()
+        };
+
+        let phi_i = fiber.phi_o + delta_phi;
+        Vector3D::new(sin_theta_i, cos_theta_i * phi_i.cos(), cos_theta_i * phi_i.sin())
+    }
+}
+
+struct Fiber {
+    sin_theta_o: f64,
+    cos_theta_o: f64,
+    phi_o: f64,
+    gamma_o: f64,
+    gamma_t: f64,
+    attenuation: [Color; LOBES],
+}
+
+impl Fiber {
+    // Azimuthal exit angle of the ideal path through lobe p.
+    fn lobe_phi(&self, p: usize) -> f64 { 2.0 * p as f64 * self.gamma_t - 2.0 * self.gamma_o + p as f64 * PI }
+
+    fn azimuthal(&self, p: usize, phi: f64, s: f64) -> f64 {
+        if p == LOBES - 1 { return 1.0 / (2.0 * PI); }
+
+        let mut delta = phi - self.lobe_phi(p);
+        delta -= 2.0 * PI * ((delta + PI) / (2.0 * PI)).floor();
+        trimmed_logistic(delta, s)
+    }
+}
+
+// Frame with x along the fiber and y across it in the direction v grows, so h and the azimuth agree on either side.
+fn fiber_frame(record: &HitRecord) -> Onb {
+    let along = record.dpdu;
+    let across = record.dpdv - along * (Vector3D::dot(&along, &record.dpdv) / along.length_squared().max(1e-300));
+    if along.near_zero() || across.near_zero() { return record.shading_frame(); }
+
+    let (along, across) = (along.normalized(), across.normalized());
+    Onb::from_axes(along, across, Vector3D::cross(&along, &across))
+}
+
+fn safe_sqrt(value: f64) -> f64 { value.max(0.0).sqrt() }
+
+// Modified Bessel function of the first kind, order zero, and its logarithm for large arguments.
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term) = (1.0, 1.0);
+    let square = x * x / 4.0;
+    for i in 1..=10 {
+        term *= square / (i * i) as f64;
+        sum += term;
+    }
+    sum
+}
+
+fn log_bessel_i0(x: f64) -> f64 {
+    if x > 12.0 { x + 0.5 * (-(2.0 * PI).ln() + (1.0 / x).ln() + 1.0 / (8.0 * x)) } else { bessel_i0(x).ln() }
+}
+
+fn longitudinal(cos_theta_i: f64, cos_theta_o: f64, sin_theta_i: f64, sin_theta_o: f64, variance: f64) -> f64 {
+    let a = cos_theta_i * cos_theta_o / variance;
+    let b = sin_theta_i * sin_theta_o / variance;
+
+    if variance <= 0.1 {
+        (log_bessel_i0(a) - b - 1.0 / variance - variance.ln()).exp()
+    } else {
+        (-b).exp() * bessel_i0(a) / ((1.0 / variance).sinh() * 2.0 * variance)
+    }
+}
+
+fn logistic(x: f64, s: f64) -> f64 {
+    let x = x.abs();
+    (-x / s).exp() / (s * (1.0 + (-x / s).exp()).powi(2))
+}
+
+fn logistic_cdf(x: f64, s: f64) -> f64 { 1.0 / (1.0 + (-x / s).exp()) }
+
+// Logistic restricted to [-pi, pi] and renormalised.
+fn trimmed_logistic(x: f64, s: f64) -> f64 { logistic(x, s) / (logistic_cdf(PI, s) - logistic_cdf(-PI, s)) }
+
+fn sample_trimmed_logistic(u: f64, s: f64) -> f64 {
+    let k = logistic_cdf(PI, s) - logistic_cdf(-PI, s);
+    let x = -s * (1.0 / (u * k + logistic_cdf(-PI, s)) - 1.0).ln();
+    x.clamp(-PI, PI)
+}
+
+impl Material for Hair {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = fiber_frame(record);
+        let fiber = self.geometry(record, &frame.to_local(&-ray_in.direction().normalized()));
+
+        let wi = self.sample_local(&fiber);
+        let pdf = self.pdf_local(&fiber, &wi);
+        if pdf <= 0.0 { return None; }
+
+        *attenuation = self.evaluate_local(&fiber, &wi) / pdf;
+        Some(Ray::new(record.point, frame.to_world(&wi), ray_in.time()))
+    }
+
+    // The fiber model already includes the projection, so the integrators' cosine against the ribbon normal is divided back out.
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = fiber_frame(record);
+        let fiber = self.geometry(record, &frame.to_local(&-direction_in.normalized()));
+
+        let direction_out = direction_out.normalized();
+        let cosine = Vector3D::dot(&direction_out, &record.normal).abs();
+        if cosine <= 1e-6 { return Color::default(); }
+
+        self.evaluate_local(&fiber, &frame.to_local(&direction_out)) / cosine
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let frame = fiber_frame(record);
+        let fiber = self.geometry(record, &frame.to_local(&-direction_in.normalized()));
+        self.pdf_local(&fiber, &frame.to_local(&direction_out.normalized()))
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+
+    fn id(&self) -> usize { self.id }
+}
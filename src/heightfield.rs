@@ -0,0 +1,333 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Arc;
+
+use image::{ImageError, ImageResult};
+
+use crate::aabb::AABB;
+use crate::hittable::{self, HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::{Point3D, Vector3D};
+
+const PADDING: f64 = 0.0001;
+const TOO_SMALL: &str = "heightfield needs at least two samples along each axis";
+
+// Lowest and highest sample under each node of a quadtree over the cells, halving in resolution per level.
+struct MinMaxLevel {
+    width: usize,
+    depth: usize,
+    values: Vec<(f32, f32)>,
+}
+
+impl MinMaxLevel {
+    fn get(&self, x: usize, z: usize) -> (f32, f32) { self.values[z * self.width + x] }
+}
+
+// Grid of height samples in [0, 1] spanning size from the corner, each cell split into two triangles with smooth shading.
+pub struct Heightfield {
+    width: usize,
+    depth: usize,
+    heights: Vec<f32>,
+    levels: Vec<MinMaxLevel>,
+    corner: Point3D,
+    size: Vector3D,
+    material: Arc<dyn Material>,
+    bounding_box: AABB,
+    id: usize,
+}
+
+impl Heightfield {
+    // Samples run along x first, one row of width samples per step along z.
+    pub fn new(width: usize, depth: usize, heights: Vec<f32>, corner: Point3D, size: Vector3D, material: Arc<dyn Material>) -> Self {
+        assert!(width >= 2 && depth >= 2, "a heightfield needs at least two samples along each axis");
+        assert_eq!(heights.len(), width * depth, "heightfield sample count does not match its resolution");
+
+        let levels = Self::build_levels(width, depth, &heights);
+        let (low, high) = levels.last().map_or((0.0, 1.0), |top| top.get(0, 0));
+
+        let bounding_box = AABB::new(
+            Interval::new(corner.x(), corner.x() + size.x()),
+            Interval::new(corner.y() + low as f64 * size.y(), corner.y() + high as f64 * size.y()),
+            Interval::new(corner.z(), corner.z() + size.z()),
+        );
+
+        Self { width, depth, heights, levels, corner, size, material, bounding_box: bounding_box.pad(PADDING), id: hittable::next_object_id() }
+    }
+
+    // Grayscale image with one sample per pixel, image rows running along z. Colors are reduced to luminance.
+    pub fn load<P: AsRef<Path>>(path: P, corner: Point3D, size: Vector3D, material: Arc<dyn Material>) -> ImageResult<Self> {
+        let image = image::open(path)?.into_luma16();
+        if image.width() < 2 || image.height() < 2 { return Err(ImageError::IoError(io::Error::new(io::ErrorKind::InvalidData, TOO_SMALL))); }
+
+        let heights = image.pixels().map(|pixel| pixel[0] as f32 / u16::MAX as f32).collect();
+
+        Ok(Self::new(image.width() as usize, image.height() as usize, heights, corner, size, material))
+    }
+
+    // Headerless little-endian unsigned 16-bit samples, as exported by most terrain tools.
+    pub fn load_raw<P: AsRef<Path>>(path: P, width: usize, depth: usize, corner: Point3D, size: Vector3D, material: Arc<dyn Material>) -> io::Result<Self> {
+        if width < 2 || depth < 2 { return Err(io::Error::new(io::ErrorKind::InvalidInput, TOO_SMALL)); }
+        let Some(length) = width.checked_mul(depth).and_then(|count| count.checked_mul(2)) else {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "heightfield resolution overflows"));
+        };
+
+        let bytes = fs::read(path)?;
+        if bytes.len() < length { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "heightfield file is truncated")); }
+
+        let heights = bytes.chunks_exact(2).take(width * depth).map(|chunk| u16::from_le_bytes([chunk[0], chunk[1]]) as f32 / u16::MAX as f32).collect();
+
+        Ok(Self::new(width, depth, heights, corner, size, material))
+    }
+
+    fn build_levels(width: usize, depth: usize, heights: &[f32]) -> Vec<MinMaxLevel> {
+        let sample = |x: usize, z: usize| heights[z * width + x];
+
+        let (cells_x, cells_z) = (width - 1, depth - 1);
+        let mut values = Vec::with_capacity(cells_x * cells_z);
+        for z in 0..cells_z {
+            for x in 0..cells_x {
+                let corners = [sample(x, z), sample(x + 1, z), sample(x, z + 1), sample(x + 1, z + 1)];
+                values.push((corners.iter().cloned().fold(f32::INFINITY, f32::min), corners.iter().cloned().fold(f32::NEG_INFINITY, f32::max)));
+            }
+        }
+
+        let mut levels = vec![MinMaxLevel { width: cells_x, depth: cells_z, values }];
+
+        while levels.last().is_some_and(|level| level.width > 1 || level.depth > 1) {
+            let below = levels.last().unwrap();
+            let (width, depth) = (below.width.div_ceil(2), below.depth.div_ceil(2));
+            let mut values = Vec::with_capacity(width * depth);
+
+            for z in 0..depth {
+                for x in 0..width {
+                    let mut range = (f32::INFINITY, f32::NEG_INFINITY);
+                    for (cx, cz) in [(2 * x, 2 * z), (2 * x + 1, 2 * z), (2 * x, 2 * z + 1), (2 * x + 1, 2 * z + 1)] {
+                        if cx >= below.width || cz >= below.depth { continue; }
+                        let (low, high) = below.get(cx, cz);
+                        range = (range.0.min(low), range.1.max(high));
+                    }
+                    values.push(range);
+                }
+            }
+
+            levels.push(MinMaxLevel { width, depth, values });
+        }
+
+        levels
+    }
+
+    fn cell_size(&self) -> (f64, f64) { (self.size.x() / (self.width - 1) as f64, self.size.z() / (self.depth - 1) as f64) }
+
+    fn vertex(&self, x: usize, z: usize) -> Point3D {
+        let (dx, dz) = self.cell_size();
+        let height = self.heights[z * self.width + x] as f64;
+        self.corner + Vector3D::new(x as f64 * dx, height * self.size.y(), z as f64 * dz)
+    }
+
+    // Smooth vertex normal from central differences, one sided at the border.
+    fn vertex_normal(&self, x: usize, z: usize) -> Vector3D {
+        let (dx, dz) = self.cell_size();
+        let sample = |x: usize, z: usize| self.heights[z * self.width + x] as f64 * self.size.y();
+
+        let (left, right) = (x.saturating_sub(1), (x + 1).min(self.width - 1));
+        let (back, front) = (z.saturating_sub(1), (z + 1).min(self.depth - 1));
+
+        let slope_x = (sample(right, z) - sample(left, z)) / ((right - left) as f64 * dx);
+        let slope_z = (sample(x, front) - sample(x, back)) / ((front - back) as f64 * dz);
+
+        Vector3D::new(-slope_x, 1.0, -slope_z).normalized()
+    }
+
+    fn node_box(&self, level: usize, x: usize, z: usize) -> AABB {
+        let (dx, dz) = self.cell_size();
+        let (low, high) = self.levels[level].get(x, z);
+        let span = 1usize << level;
+
+        let (x0, x1) = (x * span, ((x + 1) * span).min(self.width - 1));
+        let (z0, z1) = (z * span, ((z + 1) * span).min(self.depth - 1));
+
+        AABB::new(
+            Interval::new(self.corner.x() + x0 as f64 * dx, self.corner.x() + x1 as f64 * dx),
+            Interval::new(self.corner.y() + low as f64 * self.size.y(), self.corner.y() + high as f64 * self.size.y()),
+            Interval::new(self.corner.z() + z0 as f64 * dz, self.corner.z() + z1 as f64 * dz),
+        )
+        .pad(PADDING)
+    }
+
+    // Descend the quadtree front to back, skipping nodes whose height range the ray misses.
+    fn traverse(&self, ray: &Ray, level: usize, x: usize, z: usize, range: &mut Interval, closest: &mut Option<CellHit>) {
+        if !self.node_box(level, x, z).hit(ray, &mut range.clone()) { return; }
+
+        if level == 0 {
+            if let Some(hit) = self.intersect_cell(ray, x, z, range) {
+                range.max = hit.depth;
+                *closest = Some(hit);
+            }
+            return;
+        }
+
+        let below = &self.levels[level - 1];
+        let xs = if ray.direction().x() >= 0.0 { [0, 1] } else { [1, 0] };
+        let zs = if ray.direction().z() >= 0.0 { [0, 1] } else { [1, 0] };
+
+        for cx in xs {
+            for cz in zs {
+                let (child_x, child_z) = (2 * x + cx, 2 * z + cz);
+                if child_x < below.width && child_z < below.depth { self.traverse(ray, level - 1, child_x, child_z, range, closest); }
+            }
+        }
+    }
+
+    fn intersect_cell(&self, ray: &Ray, x: usize, z: usize, range: &Interval) -> Option<CellHit> {
+        let corners = [(x, z), (x + 1, z), (x + 1, z + 1), (x, z + 1)];
+        let mut closest: Option<CellHit> = None;
+
+        for triangle in [[corners[0], corners[1], corners[2]], [corners[0], corners[2], corners[3]]] {
+            let vertices = triangle.map(|(x, z)| self.vertex(x, z));
+            let bound = closest.as_ref().map_or(range.max, |hit| hit.depth);
+
+            if let Some((depth, b1, b2)) = intersect_triangle(ray, &vertices, &Interval::new(range.min, bound)) {
+                closest = Some(CellHit { depth, triangle, barycentric: [1.0 - b1 - b2, b1, b2] });
+            }
+        }
+
+        closest
+    }
+}
+
+struct CellHit {
+    depth: f64,
+    triangle: [(usize, usize); 3],
+    barycentric: [f64; 3],
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let mut range = *interval;
+        let mut closest = None;
+        self.traverse(ray, self.levels.len() - 1, 0, 0, &mut range, &mut closest);
+        let hit = closest?;
+
+        let vertices = hit.triangle.map(|(x, z)| self.vertex(x, z));
+        let mut outward = Vector3D::cross(&(vertices[1] - vertices[0]), &(vertices[2] - vertices[0])).normalized();
+        if outward.y() < 0.0 { outward = -outward; }
+
+        let shading = hit.triangle.iter().zip(hit.barycentric).fold(Vector3D::default(), |sum, (&(x, z), weight)| sum + self.vertex_normal(x, z) * weight);
+
+        let mut record = HitRecord { depth: hit.depth, time: ray.time(), point: ray.at(hit.depth), ..HitRecord::default() };
+        let local = record.point - self.corner;
+
+        record.set_face_normal(ray, &outward);
+        (record.u, record.v) = ((local.x() / self.size.x()).clamp(0.0, 1.0), (local.z() / self.size.z()).clamp(0.0, 1.0));
+
+        // Follow the triangle's slope so u and v run along x and z across the surface.
+        (record.dpdu, record.dpdv) = (
+            Vector3D::new(self.size.x(), -outward.x() / outward.y() * self.size.x(), 0.0),
+            Vector3D::new(0.0, -outward.z() / outward.y() * self.size.z(), self.size.z()),
+        );
+        record.set_shading_normal(ray, &shading);
+        record.object_id = self.id;
+        record.set_material(ray, self.material.clone());
+
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+}
+
+// Möller-Trumbore, returning the depth and the barycentric weights of the second and third vertices.
+fn intersect_triangle(ray: &Ray, vertices: &[Point3D; 3], interval: &Interval) -> Option<(f64, f64, f64)> {
+    let (edge1, edge2) = (vertices[1] - vertices[0], vertices[2] - vertices[0]);
+    let p = Vector3D::cross(&ray.direction(), &edge2);
+    let determinant = Vector3D::dot(&edge1, &p);
+    if determinant.abs() < 1e-12 { return None; }
+
+    let inverse = 1.0 / determinant;
+    let s = ray.origin() - vertices[0];
+    let b1 = Vector3D::dot(&s, &p) * inverse;
+    if !(0.0..=1.0).contains(&b1) { return None; }
+
+    let q = Vector3D::cross(&s, &edge1);
+    let b2 = Vector3D::dot(&ray.direction(), &q) * inverse;
+    if b2 < 0.0 || b1 + b2 > 1.0 { return None; }
+
+    let depth = Vector3D::dot(&edge2, &q) * inverse;
+    if interval.surrounds(depth) { Some((depth, b1, b2)) } else { None }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    // Odd resolutions leave partial nodes along both edges of every quadtree level.
+    fn terrain(rng: &mut StdRng) -> Heightfield {
+        let (width, depth) = (13, 9);
+        let heights = (0..width * depth).map(|_| rng.gen_range(0.0..1.0f32)).collect();
+        Heightfield::new(width, depth, heights, Point3D::new(-2.0, 0.0, -1.0), Vector3D::new(4.0, 1.5, 3.0), Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))))
+    }
+
+    fn brute_force(heightfield: &Heightfield, ray: &Ray) -> Option<f64> {
+        let range = Interval::new(0.001, f64::INFINITY);
+        (0..heightfield.depth - 1)
+            .flat_map(|z| (0..heightfield.width - 1).map(move |x| (x, z)))
+            .filter_map(|(x, z)| heightfield.intersect_cell(ray, x, z, &range).map(|hit| hit.depth))
+            .min_by(f64::total_cmp)
+    }
+
+    #[test]
+    fn levels_bound_every_cell_below_them() {
+        let heightfield = terrain(&mut StdRng::seed_from_u64(7));
+
+        for (level, node) in heightfield.levels.iter().enumerate() {
+            let span = 1usize << level;
+            for z in 0..node.depth {
+                for x in 0..node.width {
+                    let mut expected = (f32::INFINITY, f32::NEG_INFINITY);
+                    for sz in z * span..=((z + 1) * span).min(heightfield.depth - 1) {
+                        for sx in x * span..=((x + 1) * span).min(heightfield.width - 1) {
+                            let height = heightfield.heights[sz * heightfield.width + sx];
+                            expected = (expected.0.min(height), expected.1.max(height));
+                        }
+                    }
+
+                    assert_eq!(node.get(x, z), expected, "level {level}, node ({x}, {z})");
+                }
+            }
+        }
+
+        assert_eq!(heightfield.levels.last().map(|top| (top.width, top.depth)), Some((1, 1)));
+    }
+
+    #[test]
+    fn traversal_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let heightfield = terrain(&mut rng);
+        let mut hits = 0;
+
+        for _ in 0..2000 {
+            let origin = Point3D::new(rng.gen_range(-3.0..3.0), rng.gen_range(-0.5..3.0), rng.gen_range(-2.0..3.0));
+            let target = Point3D::new(rng.gen_range(-2.0..2.0), rng.gen_range(0.0..1.5), rng.gen_range(-1.0..2.0));
+            let ray = Ray::new(origin, target - origin, 0.0);
+
+            let expected = brute_force(&heightfield, &ray);
+            let actual = heightfield.hit(&ray, &mut Interval::new(0.001, f64::INFINITY)).map(|record| record.depth);
+
+            match (actual, expected) {
+                (Some(actual), Some(expected)) => assert!((actual - expected).abs() < 1e-9, "ray {ray:?}: {actual} against {expected}"),
+                (None, None) => {}
+                _ => panic!("ray {ray:?}: traversal found {actual:?}, brute force {expected:?}"),
+            }
+            hits += expected.is_some() as usize;
+        }
+
+        assert!(hits > 500, "only {hits} rays hit the terrain");
+    }
+}
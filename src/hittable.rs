@@ -1,67 +1,238 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+
+use rand::Rng;
+
 use crate::aabb::AABB;
+use crate::color::Color;
 
 use crate::interval::Interval;
 use crate::material::Material;
+use crate::onb::Onb;
 use crate::ray::Ray;
+use crate::spectrum::Wavelengths;
+use crate::texture::Footprint;
 use crate::vector::{Point3D, Vector3D};
 
+const MIN_SHADING_FACING: f64 = 0.01;
+const MAX_CROSSINGS: usize = 64;
+
+static NEXT_OBJECT_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub fn next_object_id() -> usize { NEXT_OBJECT_ID.fetch_add(1, Ordering::Relaxed) }
+
 pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord>;
 
     fn bounding_box(&self) -> AABB;
+
+    fn area(&self) -> f64 { 0.0 }
+
+    fn sample_surface(&self, _time: f64) -> Option<HitRecord> { None }
+
+    // Emissive primitives that sample_surface can place points on, so integrators can aim rays at them.
+    fn is_light(&self) -> bool { false }
+
+    // Containers hand every light below them to the list.
+    fn collect_lights(&self, _lights: &mut HittableList) {}
+
+    // Media sample where along the span they scatter, so containers test them after every surface has tightened the interval.
+    fn is_medium(&self) -> bool { false }
+
+    fn transmittance(&self, ray: &Ray, interval: &Interval) -> Color {
+        if self.hit(ray, &mut interval.clone()).is_some() { Color::default() } else { Color::new(1.0, 1.0, 1.0) }
+    }
+
+    // Every stretch of the ray's whole line that lies inside the solid, in order along the ray. Only meaningful for closed surfaces.
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        let mut crossings = Vec::new();
+        let mut start = f64::NEG_INFINITY;
+
+        while crossings.len() < MAX_CROSSINGS {
+            let Some(record) = self.hit(ray, &mut Interval::new(start, f64::INFINITY)) else { break };
+            start = record.depth;
+            crossings.push(record);
+        }
+
+        Span::from_crossings(crossings)
+    }
 }
 
 #[derive(Clone)]
+pub struct Span {
+    pub entry: HitRecord,
+    pub exit: HitRecord,
+}
+
+impl Span {
+    // Pair crossings sorted along the ray, matching each entering hit with the next leaving one.
+    pub fn from_crossings(crossings: impl IntoIterator<Item = HitRecord>) -> Vec<Span> {
+        let mut spans = Vec::new();
+        let mut entry: Option<HitRecord> = None;
+
+        for record in crossings {
+            if record.front_face {
+                entry.get_or_insert(record);
+            } else if let Some(entry) = entry.take() {
+                spans.push(Span { entry, exit: record });
+            }
+        }
+
+        spans
+    }
+}
+
+#[derive(Clone, Default)]
 pub struct HitRecord {
     pub point: Point3D,
     pub normal: Vector3D,
+    pub geometric_normal: Vector3D,
+    pub dpdu: Vector3D,
+    pub dpdv: Vector3D,
+    pub dndu: Vector3D,
+    pub dndv: Vector3D,
+    pub dpdx: Vector3D,
+    pub dpdy: Vector3D,
+    pub footprint: Footprint,
     pub material: Option<Arc<dyn Material>>,
     pub depth: f64,
+    pub time: f64,
+    pub u: f64,
+    pub v: f64,
     pub front_face: bool,
+    // Scattering event inside a participating medium rather than on a surface.
+    pub medium: bool,
+    pub object_id: usize,
+    pub wavelengths: Option<Wavelengths>,
 }
 
 impl HitRecord {
-    pub fn default() -> Self {
-        Self {
-            point: Point3D::default(),
-            normal: Vector3D::default(),
-            material: None,
-            depth: f64::default(),
-            front_face: bool::default(),
-        }
-    }
-
     pub fn new(
         point: Point3D,
         normal: Vector3D,
         material: Option<Arc<dyn Material>>,
         depth: f64,
+        u: f64,
+        v: f64,
         front_face: bool,
     ) -> Self {
-        Self { point, normal, material, depth, front_face }
+        Self {
+            point,
+            normal,
+            geometric_normal: normal,
+            dpdu: Vector3D::default(),
+            dpdv: Vector3D::default(),
+            dndu: Vector3D::default(),
+            dndv: Vector3D::default(),
+            dpdx: Vector3D::default(),
+            dpdy: Vector3D::default(),
+            footprint: Footprint::default(),
+            material,
+            depth,
+            time: 0.0,
+            u,
+            v,
+            front_face,
+            medium: false,
+            object_id: 0,
+            wavelengths: None,
+        }
     }
 
     pub fn set_face_normal(&mut self, ray: &Ray, outward_normal: &Vector3D) {
         self.front_face = Vector3D::dot(&ray.direction(), outward_normal) < 0.0;
-        self.normal = if self.front_face { outward_normal.clone() } else { -outward_normal.clone() };
+        self.normal = if self.front_face { *outward_normal } else { -*outward_normal };
+        self.geometric_normal = self.normal;
+    }
+
+    pub fn set_material(&mut self, ray: &Ray, material: Arc<dyn Material>) {
+        self.wavelengths = ray.wavelengths();
+        self.compute_differentials(ray);
+        if let Some(shading_normal) = material.shading_normal(self) { self.set_shading_normal(ray, &shading_normal); }
+        self.material = Some(material);
+    }
+
+    pub fn set_shading_normal(&mut self, ray: &Ray, shading_normal: &Vector3D) {
+        if shading_normal.near_zero() { return; }
+
+        let mut normal = shading_normal.normalized();
+        if Vector3D::dot(&normal, &self.geometric_normal) < 0.0 { normal = -normal; }
+
+        // Bend the shading normal back towards the geometric one until the viewer sits in front of it.
+        let view = -ray.direction().normalized();
+        let facing = Vector3D::dot(&view, &normal);
+        if facing < MIN_SHADING_FACING {
+            let geometric_facing = Vector3D::dot(&view, &self.geometric_normal).max(1e-4);
+            normal = (normal + self.geometric_normal * ((MIN_SHADING_FACING - facing) / geometric_facing)).normalized();
+        }
+
+        self.normal = normal;
+    }
+
+    pub fn compute_differentials(&mut self, ray: &Ray) {
+        let differential = match ray.differential() {
+            Some(differential) => differential,
+            None => return,
+        };
+
+        let normal = self.geometric_normal;
+        let plane = Vector3D::dot(&normal, &self.point);
+
+        let x_denominator = Vector3D::dot(&normal, &differential.rx_direction);
+        let y_denominator = Vector3D::dot(&normal, &differential.ry_direction);
+        if x_denominator == 0.0 || y_denominator == 0.0 { return; }
+
+        let tx = (plane - Vector3D::dot(&normal, &differential.rx_origin)) / x_denominator;
+        let ty = (plane - Vector3D::dot(&normal, &differential.ry_origin)) / y_denominator;
+
+        self.dpdx = differential.rx_origin + differential.rx_direction * tx - self.point;
+        self.dpdy = differential.ry_origin + differential.ry_direction * ty - self.point;
+
+        let axes = if normal.x().abs() > normal.y().abs() && normal.x().abs() > normal.z().abs() {
+            [1, 2]
+        } else if normal.y().abs() > normal.z().abs() {
+            [0, 2]
+        } else {
+            [0, 1]
+        };
+
+        let a = [[self.dpdu[axes[0]], self.dpdv[axes[0]]], [self.dpdu[axes[1]], self.dpdv[axes[1]]]];
+        let determinant = a[0][0] * a[1][1] - a[0][1] * a[1][0];
+        if determinant.abs() < 1e-12 { return; }
+
+        let solve = |b: [f64; 2]| ((a[1][1] * b[0] - a[0][1] * b[1]) / determinant, (a[0][0] * b[1] - a[1][0] * b[0]) / determinant);
+        let (dudx, dvdx) = solve([self.dpdx[axes[0]], self.dpdx[axes[1]]]);
+        let (dudy, dvdy) = solve([self.dpdy[axes[0]], self.dpdy[axes[1]]]);
+
+        self.footprint = Footprint { dudx, dvdx, dudy, dvdy };
+    }
+
+    pub fn shading_frame(&self) -> Onb {
+        let tangent = self.dpdu - self.normal * Vector3D::dot(&self.normal, &self.dpdu);
+        if tangent.near_zero() { return Onb::new(&self.normal); }
+
+        let tangent = tangent.normalized();
+        Onb::from_axes(tangent, Vector3D::cross(&self.normal, &tangent), self.normal)
     }
 }
 
+#[derive(Default)]
 pub struct HittableList {
     pub objects: Vec<Arc<dyn Hittable>>,
     bounding_box: AABB,
 }
 
 impl HittableList {
-    pub fn default() -> Self { Self { objects: vec![], bounding_box: AABB::default() } }
-
     pub fn new(object: Arc<dyn Hittable>) -> Self { Self { objects: vec![object.clone()], bounding_box: object.clone().bounding_box() } }
 
     pub fn add_object(&mut self, object: Arc<dyn Hittable>) {
         self.objects.push(object.clone());
         self.bounding_box = AABB::from_aabb_bounds(&self.bounding_box, &object.clone().bounding_box());
     }
+
+    pub fn add_lights(&mut self, object: &Arc<dyn Hittable>) {
+        if object.is_light() { self.add_object(object.clone()); } else { object.collect_lights(self); }
+    }
 }
 
 impl Hittable for HittableList {
@@ -69,7 +240,10 @@ impl Hittable for HittableList {
         let mut return_record: Option<HitRecord> = None;
         let mut closest_so_far = interval.max;
 
-        for object in &self.objects {
+        let surfaces = self.objects.iter().filter(|object| !object.is_medium());
+        let media = self.objects.iter().filter(|object| object.is_medium());
+
+        for object in surfaces.chain(media) {
             if let Some(record) = object.hit(ray, &mut Interval::new(interval.min, closest_so_far)) {
                 closest_so_far = record.depth;
 
@@ -81,4 +255,33 @@ impl Hittable for HittableList {
     }
 
     fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn area(&self) -> f64 { self.objects.iter().map(|object| object.area()).sum() }
+
+    fn collect_lights(&self, lights: &mut HittableList) { self.objects.iter().for_each(|object| lights.add_lights(object)); }
+
+    fn is_medium(&self) -> bool { self.objects.iter().any(|object| object.is_medium()) }
+
+    fn sample_surface(&self, time: f64) -> Option<HitRecord> {
+        let mut remaining = rand::thread_rng().gen_range(0.0..1.0) * self.area();
+
+        for object in &self.objects {
+            let area = object.area();
+            if area > 0.0 && remaining < area { return object.sample_surface(time); }
+            remaining -= area;
+        }
+
+        None
+    }
+
+    fn transmittance(&self, ray: &Ray, interval: &Interval) -> Color {
+        let mut transmittance = Color::new(1.0, 1.0, 1.0);
+
+        for object in &self.objects {
+            transmittance = transmittance * object.transmittance(ray, interval);
+            if transmittance.near_zero() { return Color::default(); }
+        }
+
+        transmittance
+    }
 }
@@ -0,0 +1,318 @@
+use std::sync::Arc;
+
+use crate::bdpt::BidirectionalPathTracer;
+use crate::bvh;
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::film::Film;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::photon::PhotonMapper;
+use crate::ray::Ray;
+use crate::vector::Vector3D;
+
+pub trait Integrator: Send + Sync {
+    fn ray_color(&self, ray: &Ray, camera: &Camera, world: &dyn Hittable, film: &Film) -> Color;
+
+    fn passes(&self, _samples_per_pixel: usize) -> usize { 1 }
+
+    // Called once before rendering so integrators that sample emitters can gather them from the scene.
+    fn prepare(&mut self, _world: &dyn Hittable) {}
+
+    fn begin_pass(&mut self, _world: &dyn Hittable, _pass: usize) {}
+
+    // Whether ray_color understands rays carrying sampled wavelengths.
+    fn spectral(&self) -> bool { false }
+}
+
+pub fn from_name(name: &str) -> Option<Box<dyn Integrator>> {
+    match name {
+        "path" => Some(Box::new(PathTracer)),
+        "direct" => Some(Box::new(DirectLighting::default())),
+        "bdpt" => Some(Box::new(BidirectionalPathTracer::default())),
+        "photon" => Some(Box::new(PhotonMapper::new(100_000, 0.1))),
+        "ao" => Some(Box::new(AmbientOcclusion::new(16, 1.0))),
+        "normals" => Some(Box::new(NormalView)),
+        "depth" => Some(Box::new(DepthView::new(20.0))),
+        "material" => Some(Box::new(MaterialIdView)),
+        "object" => Some(Box::new(ObjectIdView)),
+        "uv" => Some(Box::new(UvView)),
+        "bvh" => Some(Box::new(BvhCostView::new(64))),
+        _ => None,
+    }
+}
+
+fn trace(ray: &Ray, max_depth: usize, camera: &Camera, world: &dyn Hittable) -> Color {
+    if max_depth == 0 { return Color::default(); }
+
+    if let Some(record) = world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+        return match &record.material {
+            None => Color::default(),
+
+            Some(material) => {
+                let emitted = material.emitted(&record);
+                let mut attenuation = Color::default();
+
+                let radiance = if let Some(scattered) = material.scatter(ray, &record, &mut attenuation) {
+                    emitted + attenuation * trace(&scattered.inherit(ray), max_depth - 1, camera, world)
+                } else {
+                    emitted
+                };
+                radiance * ray.transmittance(&record.point)
+            }
+        };
+    }
+
+    camera.background(ray)
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct PathTracer;
+
+impl Integrator for PathTracer {
+    fn ray_color(&self, ray: &Ray, camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        trace(ray, camera.max_depth, camera, world)
+    }
+
+    fn spectral(&self) -> bool { true }
+}
+
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (pdf, other) = (pdf * pdf, other * other);
+    if pdf + other > 0.0 { pdf / (pdf + other) } else { 0.0 }
+}
+
+// Follows specular chains from the camera, then lights the first diffuse hit with one emitter sample and one BSDF sample.
+#[derive(Default)]
+pub struct DirectLighting {
+    lights: HittableList,
+}
+
+impl DirectLighting {
+    // Solid angle density of picking a light point `distance` away along unit `direction` when the lights are sampled by area.
+    fn light_pdf(&self, distance: f64, normal: &Vector3D, direction: &Vector3D) -> f64 {
+        let area = self.lights.area();
+        let cosine = Vector3D::dot(normal, direction).abs();
+        if area <= 0.0 || cosine <= 0.0 { return 0.0; }
+
+        distance.powi(2) / (cosine * area)
+    }
+
+    // Emitters missing from the light list can only be found by BSDF sampling and keep their full weight.
+    fn is_light(&self, ray: &Ray, record: &HitRecord) -> bool {
+        self.lights.hit(ray, &mut Interval::new(0.001, f64::INFINITY)).is_some_and(|light| light.object_id == record.object_id)
+    }
+
+    fn sample_light(&self, ray: &Ray, record: &HitRecord, material: &Arc<dyn Material>, world: &dyn Hittable) -> Color {
+        let Some(light) = self.lights.sample_surface(ray.time()) else { return Color::default() };
+        let light = HitRecord { wavelengths: ray.wavelengths(), ..light };
+        let Some(emitter) = &light.material else { return Color::default() };
+
+        let offset = light.point - record.point;
+        let distance = offset.length();
+        if distance <= 0.002 { return Color::default(); }
+
+        let shadow_ray = Ray::new(record.point, offset / distance, ray.time()).with_wavelengths(ray.wavelengths());
+        let pdf_light = self.light_pdf(distance, &light.normal, &shadow_ray.direction());
+        if pdf_light <= 0.0 { return Color::default(); }
+
+        let direction_in = ray.direction().normalized();
+        let cosine = if record.medium { 1.0 } else { Vector3D::dot(&record.normal, &shadow_ray.direction()).abs() };
+        let contribution = material.evaluate(record, &direction_in, &shadow_ray.direction()) * emitter.emitted(&light) * (cosine / pdf_light);
+        if contribution.near_zero() { return Color::default(); }
+
+        let weight = power_heuristic(pdf_light, material.scattering_pdf(record, &direction_in, &shadow_ray.direction()));
+        contribution * world.transmittance(&shadow_ray, &Interval::new(0.001, distance - 0.001)) * weight
+    }
+
+    fn sample_material(&self, ray: &Ray, record: &HitRecord, material: &Arc<dyn Material>, camera: &Camera, world: &dyn Hittable) -> Color {
+        let mut attenuation = Color::default();
+        let Some(scattered) = material.scatter(ray, record, &mut attenuation) else { return Color::default() };
+        let scattered = scattered.inherit(ray);
+
+        let direction_in = ray.direction().normalized();
+        let direction_out = scattered.direction().normalized();
+        let pdf = material.scattering_pdf(record, &direction_in, &direction_out);
+        if pdf <= 0.0 { return Color::default(); }
+
+        let cosine = if record.medium { 1.0 } else { Vector3D::dot(&record.normal, &direction_out).abs() };
+        let throughput = material.evaluate(record, &direction_in, &direction_out) * (cosine / pdf);
+
+        match world.hit(&scattered, &mut Interval::new(0.001, f64::INFINITY)) {
+            None => throughput * camera.background(&scattered),
+            Some(hit) => {
+                let emitted = hit.material.as_ref().map_or(Color::default(), |emitter| emitter.emitted(&hit)) * scattered.transmittance(&hit.point);
+                if emitted.near_zero() { return Color::default(); }
+
+                let distance = hit.depth * scattered.direction().length();
+                let weight = if self.is_light(&scattered, &hit) { power_heuristic(pdf, self.light_pdf(distance, &hit.normal, &direction_out)) } else { 1.0 };
+                throughput * emitted * weight
+            }
+        }
+    }
+}
+
+impl Integrator for DirectLighting {
+    fn ray_color(&self, ray: &Ray, camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        let mut ray = *ray;
+        let mut beta = Color::new(1.0, 1.0, 1.0);
+        let mut color = Color::default();
+
+        for _ in 0..camera.max_depth {
+            let Some(record) = world.hit(&ray, &mut Interval::new(0.001, f64::INFINITY)) else { return color + beta * camera.background(&ray) };
+            let Some(material) = record.material.clone() else { return color };
+            beta = beta * ray.transmittance(&record.point);
+            color += beta * material.emitted(&record);
+
+            if !material.is_specular(&record) {
+                return color + beta * (self.sample_light(&ray, &record, &material, world) + self.sample_material(&ray, &record, &material, camera, world));
+            }
+
+            let mut attenuation = Color::default();
+            let Some(scattered) = material.scatter(&ray, &record, &mut attenuation) else { return color };
+
+            beta = beta * attenuation;
+            ray = scattered.inherit(&ray);
+        }
+
+        color
+    }
+
+    fn prepare(&mut self, world: &dyn Hittable) {
+        self.lights = HittableList::default();
+        world.collect_lights(&mut self.lights);
+    }
+
+    fn spectral(&self) -> bool { true }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct AmbientOcclusion {
+    samples: usize,
+    distance: f64,
+}
+
+impl AmbientOcclusion {
+    pub fn new(samples: usize, distance: f64) -> Self { Self { samples, distance } }
+}
+
+impl Integrator for AmbientOcclusion {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        let record = match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+            Some(record) => record,
+            None => return Color::new(1.0, 1.0, 1.0),
+        };
+
+        let unoccluded = (0..self.samples).filter(|_| {
+            let mut direction = record.normal + Vector3D::random_normal();
+            if direction.near_zero() { direction = record.normal; }
+
+            let occlusion_ray = Ray::new(record.point, direction.normalized(), ray.time());
+            world.hit(&occlusion_ray, &mut Interval::new(0.001, self.distance)).is_none()
+        }).count();
+
+        Color::new(1.0, 1.0, 1.0) * (unoccluded as f64 / self.samples as f64)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct NormalView;
+
+impl Integrator for NormalView {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+            Some(record) => (record.normal + Color::new(1.0, 1.0, 1.0)) * 0.5,
+            None => Color::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct DepthView {
+    max_distance: f64,
+}
+
+impl DepthView {
+    pub fn new(max_distance: f64) -> Self { Self { max_distance } }
+}
+
+impl Integrator for DepthView {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+            Some(record) => {
+                let distance = record.depth * ray.direction().length();
+                Color::new(1.0, 1.0, 1.0) * (1.0 - Interval::new(0.0, 1.0).clamp(distance / self.max_distance))
+            }
+            None => Color::default(),
+        }
+    }
+}
+
+fn id_color(id: usize) -> Color {
+    let mut hash = (id as u64).wrapping_add(0x9e3779b97f4a7c15);
+    hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+    hash ^= hash >> 31;
+
+    Color::new(
+        (hash & 0xff) as f64 / 255.0,
+        ((hash >> 8) & 0xff) as f64 / 255.0,
+        ((hash >> 16) & 0xff) as f64 / 255.0,
+    )
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct MaterialIdView;
+
+impl Integrator for MaterialIdView {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)).and_then(|record| record.material) {
+            Some(material) if material.id() != 0 => id_color(material.id()),
+            _ => Color::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct ObjectIdView;
+
+impl Integrator for ObjectIdView {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+            Some(record) if record.object_id != 0 => id_color(record.object_id),
+            _ => Color::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default)]
+pub struct UvView;
+
+impl Integrator for UvView {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+            Some(record) => Color::new(record.u, record.v, 0.0),
+            None => Color::default(),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone)]
+pub struct BvhCostView {
+    max_visits: usize,
+}
+
+impl BvhCostView {
+    pub fn new(max_visits: usize) -> Self { Self { max_visits } }
+}
+
+impl Integrator for BvhCostView {
+    fn ray_color(&self, ray: &Ray, _camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        bvh::reset_node_visits();
+        world.hit(ray, &mut Interval::new(0.001, f64::INFINITY));
+
+        let cost = Interval::new(0.0, 1.0).clamp(bvh::node_visits() as f64 / self.max_visits as f64);
+        Color::new(0.0, 0.0, 1.0) * (1.0 - cost) + Color::new(1.0, 0.0, 0.0) * cost
+    }
+}
@@ -1,10 +1,31 @@
 pub mod aabb;
+pub mod bdpt;
 pub mod bvh;
 pub mod camera;
 pub mod color;
+pub mod csg;
+pub mod curve;
+pub mod film;
+pub mod grid;
+pub mod hair;
+pub mod heightfield;
 pub mod hittable;
+pub mod integrator;
 pub mod interval;
 pub mod material;
+pub mod measured;
+pub mod medium;
+pub mod microfacet;
+pub mod noise;
+pub mod onb;
+pub mod photon;
+pub mod quad;
+pub mod quadric;
 pub mod ray;
+pub mod sdf;
+pub mod spectrum;
 pub mod sphere;
+pub mod subsurface;
+pub mod texture;
+pub mod transform;
 pub mod vector;
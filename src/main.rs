@@ -1,10 +1,13 @@
+use std::env;
 use std::sync::Arc;
 
 use rand::Rng;
 
+use halide::bvh::BVHNode;
 use halide::camera::Camera;
 use halide::color::Color;
 use halide::hittable::HittableList;
+use halide::integrator;
 use halide::material::{Dielectric, Lambertian, Material, Metal};
 use halide::sphere::Sphere;
 use halide::vector::{Point3D, Vector3D};
@@ -36,10 +39,10 @@ fn main() {
                     Arc::new(Dielectric::new(1.5))
                 };
 
-                if center2.is_none() {
-                    world.add_object(Arc::new(Sphere::new_static(center, 0.2, sphere_material)));
+                if let Some(center2) = center2 {
+                    world.add_object(Arc::new(Sphere::new_dynamic(center, center2.normalized(), 0.2, sphere_material)));
                 } else {
-                    world.add_object(Arc::new(Sphere::new_dynamic(center, center2.unwrap().normalized(), 0.2, sphere_material)));
+                    world.add_object(Arc::new(Sphere::new_static(center, 0.2, sphere_material)));
                 }
             }
         }
@@ -53,7 +56,7 @@ fn main() {
     world.add_object(Arc::new(Sphere::new_static(Point3D::new(-4.0, 1.0, 0.0), 1.0, material_2)));
     world.add_object(Arc::new(Sphere::new_static(Point3D::new(4.0, 1.0, 0.0), 1.0, material_3)));
 
-    // world = HittableList::new(Arc::new(BVHNode::from_hittable_list(&world)));
+    world = HittableList::new(Arc::new(BVHNode::from_hittable_list(&world)));
 
     let mut camera = Camera::new(
         16.0 / 9.0,
@@ -68,5 +71,14 @@ fn main() {
         10.0,
     );
 
-    camera.render_parallel(Arc::new(&world));
+    // Usage: halide [integrator] [--spectral]
+    for argument in env::args().skip(1) {
+        if argument == "--spectral" {
+            camera.spectral = true;
+        } else {
+            camera.integrator = integrator::from_name(&argument).unwrap_or_else(|| panic!("Unknown integrator '{argument}'."));
+        }
+    }
+
+    camera.render_parallel(&world);
 }
@@ -1,98 +1,1410 @@
+use std::f64::consts::PI;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
 use rand::Rng;
 
-use crate::color::Color;
-use crate::ray::Ray;
+use crate::color::{self, Color};
+use crate::hittable::HitRecord;
+use crate::microfacet::{self, TrowbridgeReitz};
+use crate::onb::Onb;
+use crate::ray::{Ray, RayDifferential};
+use crate::spectrum::{self, Dispersion};
+use crate::texture::{ChannelTexture, ScaleTexture, SolidColor, Texture};
 use crate::vector::Vector3D;
 
-pub trait Material {
-    fn scatter(&self, ray_in: &Ray, record_point: &Vector3D, record_normal: &Vector3D, record_front_face: bool, attenuation: &mut Color) -> Option<Ray>;
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray>;
+
+    fn emitted(&self, _record: &HitRecord) -> Color { Color::default() }
+
+    fn is_emissive(&self) -> bool { false }
+
+    fn evaluate(&self, _record: &HitRecord, _direction_in: &Vector3D, _direction_out: &Vector3D) -> Color { Color::default() }
+
+    fn scattering_pdf(&self, _record: &HitRecord, _direction_in: &Vector3D, _direction_out: &Vector3D) -> f64 { 0.0 }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { true }
+
+    fn adjoint_correction(&self, _record: &HitRecord, _direction_out: &Vector3D) -> f64 { 1.0 }
+
+    fn shading_normal(&self, _record: &HitRecord) -> Option<Vector3D> { None }
+
+    // Stable identifier for debug views; zero for materials created on the fly during rendering.
+    fn id(&self) -> usize { 0 }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+static NEXT_MATERIAL_ID: AtomicUsize = AtomicUsize::new(1);
+
+pub fn next_material_id() -> usize { NEXT_MATERIAL_ID.fetch_add(1, Ordering::Relaxed) }
+
+fn normal_differentials(record: &HitRecord) -> (Vector3D, Vector3D) {
+    let footprint = &record.footprint;
+    (record.dndu * footprint.dudx + record.dndv * footprint.dvdx, record.dndu * footprint.dudy + record.dndv * footprint.dvdy)
+}
+
+fn reflected_differential(ray_in: &Ray, record: &HitRecord, direction: &Vector3D) -> Option<RayDifferential> {
+    let differential = ray_in.differential()?;
+
+    let normal = record.normal;
+    let direction = direction.normalized();
+    let outgoing = -ray_in.direction().normalized();
+    let (dndx, dndy) = normal_differentials(record);
+
+    let dwodx = -differential.rx_direction.normalized() - outgoing;
+    let dwody = -differential.ry_direction.normalized() - outgoing;
+    let ddndx = Vector3D::dot(&dwodx, &normal) + Vector3D::dot(&outgoing, &dndx);
+    let ddndy = Vector3D::dot(&dwody, &normal) + Vector3D::dot(&outgoing, &dndy);
+    let cosine = Vector3D::dot(&outgoing, &normal);
+
+    Some(RayDifferential {
+        rx_origin: record.point + record.dpdx,
+        rx_direction: direction - dwodx + (dndx * cosine + normal * ddndx) * 2.0,
+        ry_origin: record.point + record.dpdy,
+        ry_direction: direction - dwody + (dndy * cosine + normal * ddndy) * 2.0,
+    })
+}
+
+fn refracted_differential(ray_in: &Ray, record: &HitRecord, direction: &Vector3D, refraction_ratio: f64) -> Option<RayDifferential> {
+    let differential = ray_in.differential()?;
+
+    let normal = record.normal;
+    let direction = direction.normalized();
+    let outgoing = -ray_in.direction().normalized();
+    let (dndx, dndy) = normal_differentials(record);
+
+    let dwodx = -differential.rx_direction.normalized() - outgoing;
+    let dwody = -differential.ry_direction.normalized() - outgoing;
+    let ddndx = Vector3D::dot(&dwodx, &normal) + Vector3D::dot(&outgoing, &dndx);
+    let ddndy = Vector3D::dot(&dwody, &normal) + Vector3D::dot(&outgoing, &dndy);
+
+    let cosine_in = Vector3D::dot(&outgoing, &normal);
+    let cosine_out = Vector3D::dot(&direction, &normal).abs();
+    if cosine_out == 0.0 { return None; }
+
+    let mu = refraction_ratio * cosine_in - cosine_out;
+    let dmu = refraction_ratio - refraction_ratio.powi(2) * cosine_in / cosine_out;
+
+    Some(RayDifferential {
+        rx_origin: record.point + record.dpdx,
+        rx_direction: direction - dwodx * refraction_ratio + dndx * mu + normal * (dmu * ddndx),
+        ry_origin: record.point + record.dpdy,
+        ry_direction: direction - dwody * refraction_ratio + dndy * mu + normal * (dmu * ddndy),
+    })
+}
+
+fn texture_value(texture: &Arc<dyn Texture>, record: &HitRecord) -> Color {
+    texture.filtered(record.u, record.v, &record.point, record.time, &record.footprint)
+}
+
+// Colors become per-wavelength values when the record carries spectral samples; data textures stay in RGB.
+fn spectral_reflectance(record: &HitRecord, rgb: &Color) -> Color {
+    match &record.wavelengths {
+        Some(wavelengths) => wavelengths.reflectance(rgb),
+        None => *rgb,
+    }
+}
+
+fn spectral_unbounded(record: &HitRecord, rgb: &Color) -> Color {
+    match &record.wavelengths {
+        Some(wavelengths) => wavelengths.unbounded(rgb),
+        None => *rgb,
+    }
+}
+
+fn albedo_value(texture: &Arc<dyn Texture>, record: &HitRecord) -> Color { spectral_reflectance(record, &texture_value(texture, record)) }
+
+const BUMP_DELTA: f64 = 5e-4;
+
+// Shading normals can tilt a sampled direction through the real surface, so a reflection must stay on the incoming side of
+// the geometric normal and a transmission must cross it; anything else is absorbed.
+pub(crate) fn geometric_side(ray_in: &Ray, record: &HitRecord, scattered: Ray) -> Option<Ray> {
+    let crosses = |normal: &Vector3D| Vector3D::dot(&ray_in.direction(), normal) * Vector3D::dot(&scattered.direction(), normal) > 0.0;
+    if crosses(&record.normal) == crosses(&record.geometric_normal) { Some(scattered) } else { None }
+}
+
+fn same_hemisphere(direction_in: &Vector3D, record_normal: &Vector3D, direction_out: &Vector3D) -> bool {
+    Vector3D::dot(direction_in, record_normal) * Vector3D::dot(direction_out, record_normal) < 0.0
+}
+
+#[derive(Clone)]
 pub struct Lambertian {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
+    id: usize,
 }
 
 impl Lambertian {
     pub fn new(albedo: Color) -> Self {
-        Self { albedo }
+        Self::from_texture(Arc::new(SolidColor::new(albedo)))
     }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>) -> Self { Self { albedo, id: next_material_id() } }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, ray_in: &Ray, record_point: &Vector3D, record_normal: &Vector3D, _record_front_face: bool, attenuation: &mut Color) -> Option<Ray> {
-        let mut scatter_direction = *record_normal + Vector3D::random_normal();
-        if scatter_direction.near_zero() { scatter_direction = record_normal.clone(); }
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let mut scatter_direction = record.normal + Vector3D::random_normal();
+        if scatter_direction.near_zero() { scatter_direction = record.normal; }
 
-        let scattered = Ray::new(record_point.clone(), scatter_direction, ray_in.time());
+        let scattered = Ray::new(record.point, scatter_direction, ray_in.time());
+        *attenuation = albedo_value(&self.albedo, record);
 
-        attenuation[0] = self.albedo.x();
-        attenuation[1] = self.albedo.y();
-        attenuation[2] = self.albedo.z();
+        geometric_side(ray_in, record, scattered)
+    }
 
-        Some(scattered)
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        if same_hemisphere(direction_in, &record.normal, direction_out) { albedo_value(&self.albedo, record) / PI } else { Color::default() }
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        if !same_hemisphere(direction_in, &record.normal, direction_out) { return 0.0; }
+        Vector3D::dot(&direction_out.normalized(), &record.normal).abs() / PI
     }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+
+    fn id(&self) -> usize { self.id }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Clone)]
+pub struct OrenNayar {
+    albedo: Arc<dyn Texture>,
+    a: f64,
+    b: f64,
+    id: usize,
+}
+
+impl OrenNayar {
+    pub fn new(albedo: Color, sigma: f64) -> Self { Self::from_texture(Arc::new(SolidColor::new(albedo)), sigma) }
+
+    // Sigma is the standard deviation of the microfacet slope angle, in degrees.
+    pub fn from_texture(albedo: Arc<dyn Texture>, sigma: f64) -> Self {
+        let sigma2 = sigma.to_radians().powi(2);
+        Self { albedo, a: 1.0 - sigma2 / (2.0 * (sigma2 + 0.33)), b: 0.45 * sigma2 / (sigma2 + 0.09), id: next_material_id() }
+    }
+
+    fn reflectance(&self, wo: &Vector3D, wi: &Vector3D) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return 0.0; }
+
+        let sin_theta_o = (1.0 - wo.z().powi(2)).max(0.0).sqrt();
+        let sin_theta_i = (1.0 - wi.z().powi(2)).max(0.0).sqrt();
+
+        let max_cosine = if sin_theta_o > 1e-4 && sin_theta_i > 1e-4 {
+            ((wi.x() * wo.x() + wi.y() * wo.y()) / (sin_theta_i * sin_theta_o)).max(0.0)
+        } else {
+            0.0
+        };
+
+        let (sin_alpha, tan_beta) = if wi.z() > wo.z() { (sin_theta_o, sin_theta_i / wi.z()) } else { (sin_theta_i, sin_theta_o / wo.z()) };
+        (self.a + self.b * max_cosine * sin_alpha * tan_beta) / PI
+    }
+}
+
+impl Material for OrenNayar {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = record.shading_frame();
+        let wo = frame.to_local(&-ray_in.direction().normalized());
+        let wi = cosine_hemisphere();
+
+        *attenuation = albedo_value(&self.albedo, record) * (self.reflectance(&wo, &wi) * PI);
+        geometric_side(ray_in, record, Ray::new(record.point, frame.to_world(&wi), ray_in.time()))
+    }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = record.shading_frame();
+        let reflectance = self.reflectance(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()));
+
+        albedo_value(&self.albedo, record) * reflectance
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        if !same_hemisphere(direction_in, &record.normal, direction_out) { return 0.0; }
+        Vector3D::dot(&direction_out.normalized(), &record.normal).abs() / PI
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
 pub struct Metal {
-    albedo: Color,
+    albedo: Arc<dyn Texture>,
     fuzz: f64,
+    id: usize,
 }
 
 impl Metal {
-    pub fn new(albedo: Color, fuzz: f64) -> Self { Self { albedo, fuzz } }
+    pub fn new(albedo: Color, fuzz: f64) -> Self { Self::from_texture(Arc::new(SolidColor::new(albedo)), fuzz) }
+
+    pub fn from_texture(albedo: Arc<dyn Texture>, fuzz: f64) -> Self { Self { albedo, fuzz, id: next_material_id() } }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray_in: &Ray, record_point: &Vector3D, record_normal: &Vector3D, _record_front_face: bool, attenuation: &mut Color) -> Option<Ray> {
-        let reflected = Vector3D::reflect(&ray_in.direction().normalized(), record_normal);
-        let scattered = Ray::new(record_point.clone(), reflected + Vector3D::random_normal() * self.fuzz, ray_in.time());
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let reflected = Vector3D::reflect(&ray_in.direction().normalized(), &record.normal);
+        let scattered = Ray::new(record.point, reflected + Vector3D::random_normal() * self.fuzz, ray_in.time())
+            .with_differential(reflected_differential(ray_in, record, &reflected));
+
+        *attenuation = albedo_value(&self.albedo, record);
+
+        if Vector3D::dot(&scattered.direction(), &record.normal) > 0.0 { geometric_side(ray_in, record, scattered) } else { None }
+    }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
+pub struct ThinFilm {
+    thickness: Arc<dyn Texture>,
+    refractive_index: f64,
+}
+
+impl ThinFilm {
+    // Thickness in nanometres.
+    pub fn new(thickness: f64, refractive_index: f64) -> Self { Self::from_texture(constant(thickness), refractive_index) }
+
+    pub fn from_texture(thickness: Arc<dyn Texture>, refractive_index: f64) -> Self { Self { thickness, refractive_index } }
 
-        attenuation[0] = self.albedo.x();
-        attenuation[1] = self.albedo.y();
-        attenuation[2] = self.albedo.z();
+    // Reflectance of the film over a substrate of complex index eta + ik, seen from a medium of index outside.
+    fn reflectance(&self, record: &HitRecord, cosine: f64, outside: f64, eta: &Color, k: &Color) -> Color {
+        let thickness = scalar_value(&self.thickness, record).max(0.0);
+        let film = |lambda: f64, eta: f64, k: f64| microfacet::fresnel_thin_film(cosine, outside, self.refractive_index, eta, k, thickness, lambda);
 
-        if Vector3D::dot(&scattered.direction(), record_normal) > 0.0 { Some(scattered) } else { None }
+        match &record.wavelengths {
+            Some(wavelengths) => {
+                let (lambda, eta, k) = (wavelengths.lambda(), wavelengths.unbounded(eta), wavelengths.unbounded(k));
+                Color::new(film(lambda[0], eta[0], k[0]), film(lambda[1], eta[1], k[1]), film(lambda[2], eta[2], k[2]))
+            }
+            None => spectrum::reflectance_to_rgb(|lambda| film(lambda, spectrum::unbounded(eta, lambda), spectrum::unbounded(k, lambda))),
+        }
     }
 }
 
 pub struct Dielectric {
     refractive_index: f64,
+    absorption: Color,
+    dispersion: Option<Dispersion>,
+    thin_film: Option<ThinFilm>,
+    id: usize,
 }
 
 impl Dielectric {
     pub fn new(refractive_index: f64) -> Self {
-        Self { refractive_index }
+        Self { refractive_index, absorption: Color::default(), dispersion: None, thin_film: None, id: next_material_id() }
+    }
+
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    // Without spectral sampling the index is evaluated at 550nm.
+    pub fn with_dispersion(mut self, dispersion: Dispersion) -> Self {
+        self.refractive_index = dispersion.index(550.0);
+        self.dispersion = Some(dispersion);
+        self
+    }
+
+    pub fn with_absorption(mut self, absorption: Color) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Absorption chosen so that light keeps the given color after travelling the given distance inside.
+    // A non-positive distance is clamped to 1e-6, which leaves any tinted channel all but opaque.
+    pub fn with_transmittance(self, color: Color, distance: f64) -> Self {
+        let distance = distance.max(1e-6);
+        let coefficient = |channel: f64| -channel.clamp(1e-6, 1.0).ln() / distance;
+        self.with_absorption(Color::new(coefficient(color.x()), coefficient(color.y()), coefficient(color.z())))
     }
 
     pub fn reflectance(cosine: f64, refraction_ratio: f64) -> f64 {
         let r0 = ((1.0 - refraction_ratio) / (1.0 + refraction_ratio)).powi(2);
         r0 + (1.0 - r0) * (1.0 - cosine).powi(5)
     }
+
+    fn refractive_index(&self, record: &HitRecord) -> f64 {
+        match (&self.dispersion, &record.wavelengths) {
+            (Some(dispersion), Some(wavelengths)) => dispersion.index(wavelengths.hero()),
+            _ => self.refractive_index,
+        }
+    }
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray_in: &Ray, record_point: &Vector3D, record_normal: &Vector3D, record_front_face: bool, attenuation: &mut Color) -> Option<Ray> {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
         attenuation[0] = 1.0;
         attenuation[1] = 1.0;
         attenuation[2] = 1.0;
 
-        let refraction_ratio = if record_front_face { 1.0 / self.refractive_index } else { self.refractive_index };
+        // Dispersion bends each wavelength differently, so only the hero wavelength carries on.
+        let mut wavelengths = None;
+        if let (Some(_), Some(sampled)) = (&self.dispersion, &record.wavelengths) {
+            if !sampled.is_terminated() {
+                *attenuation = *attenuation * Color::new(3.0, 0.0, 0.0);
+                wavelengths = Some(sampled.terminated());
+            }
+        }
+
+        let refractive_index = self.refractive_index(record);
+        let refraction_ratio = if record.front_face { 1.0 / refractive_index } else { refractive_index };
         let direction_normal = ray_in.direction().normalized();
 
-        let cos_theta = Vector3D::dot(&-direction_normal, record_normal).min(1.0);
+        let cos_theta = Vector3D::dot(&-direction_normal, &record.normal).min(1.0);
         let sin_theta = (1.0 - cos_theta.powi(2)).sqrt();
 
         let mut rng = rand::thread_rng();
 
         let cannot_refract = refraction_ratio * sin_theta > 1.0;
-        let direction = if cannot_refract || Self::reflectance(cos_theta, refraction_ratio) > rng.gen_range(0.0..1.0) {
-            Vector3D::reflect(&direction_normal, record_normal)
+        let fresnel_cosine = if refraction_ratio > 1.0 && !cannot_refract {
+            (1.0 - (refraction_ratio * sin_theta).powi(2)).sqrt()
+        } else {
+            cos_theta
+        };
+
+        // A film reflects each wavelength differently, so branch on the average and reweight the chosen side.
+        let film = self.thin_film.as_ref().map(|film| {
+            let (outside, inside) = if record.front_face { (1.0, refractive_index) } else { (refractive_index, 1.0) };
+            film.reflectance(record, cos_theta, outside, &Color::new(inside, inside, inside), &Color::default())
+        });
+
+        let reflectance = match &film {
+            Some(film) => color::average(film),
+            None => Self::reflectance(fresnel_cosine, refraction_ratio),
+        };
+        let reflected = cannot_refract || reflectance > rng.gen_range(0.0..1.0);
+
+        if let Some(film) = film {
+            let weight = if reflected { film / reflectance.max(1e-6) } else { (Color::new(1.0, 1.0, 1.0) - film) / (1.0 - reflectance) };
+            *attenuation = *attenuation * weight;
+        }
+
+        let (direction, differential) = if reflected {
+            let direction = Vector3D::reflect(&direction_normal, &record.normal);
+            (direction, reflected_differential(ray_in, record, &direction))
+        } else {
+            let direction = Vector3D::refract(&direction_normal, &record.normal, refraction_ratio);
+            (direction, refracted_differential(ray_in, record, &direction, refraction_ratio))
+        };
+
+        // Refraction swaps the interior the integrators charge along the next segment, while a reflection stays in the one it came from.
+        let interior = match (reflected, record.front_face) {
+            (true, _) => None,
+            (false, true) => Some(self.absorption),
+            (false, false) => Some(Color::default()),
+        };
+        let scattered = Ray::new(record.point, direction, ray_in.time()).with_differential(differential).with_wavelengths(wavelengths);
+        geometric_side(ray_in, record, scattered.with_absorption(interior))
+    }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 {
+        if Vector3D::dot(direction_out, &record.normal) >= 0.0 { return 1.0; }
+
+        let refractive_index = self.refractive_index(record);
+        let refraction_ratio = if record.front_face { 1.0 / refractive_index } else { refractive_index };
+        refraction_ratio.powi(2)
+    }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
+pub struct Conductor {
+    eta: Color,
+    k: Color,
+    distribution: TrowbridgeReitz,
+    tangent_map: Option<Arc<dyn Texture>>,
+    thin_film: Option<ThinFilm>,
+    id: usize,
+}
+
+impl Conductor {
+    pub fn new(eta: Color, k: Color, roughness: f64) -> Self {
+        Self { eta, k, distribution: TrowbridgeReitz::from_roughness(roughness), tangent_map: None, thin_film: None, id: next_material_id() }
+    }
+
+    // Complex indices of refraction sampled at roughly 650, 550 and 450 nm.
+    pub fn gold(roughness: f64) -> Self { Self::new(Color::new(0.143, 0.374, 1.442), Color::new(3.983, 2.385, 1.603), roughness) }
+
+    pub fn copper(roughness: f64) -> Self { Self::new(Color::new(0.200, 0.924, 1.102), Color::new(3.912, 2.452, 2.142), roughness) }
+
+    pub fn aluminum(roughness: f64) -> Self { Self::new(Color::new(1.657, 0.880, 0.521), Color::new(9.224, 6.270, 4.837), roughness) }
+
+    pub fn silver(roughness: f64) -> Self { Self::new(Color::new(0.155, 0.117, 0.138), Color::new(4.828, 3.122, 2.147), roughness) }
+
+    // Roughness along the surface tangent (dpdu, or the tangent map) and along the bitangent.
+    pub fn with_anisotropy(mut self, roughness_u: f64, roughness_v: f64) -> Self {
+        self.distribution = TrowbridgeReitz::new(TrowbridgeReitz::roughness_to_alpha(roughness_u), TrowbridgeReitz::roughness_to_alpha(roughness_v));
+        self
+    }
+
+    pub fn with_tangent_map(mut self, tangent_map: Arc<dyn Texture>) -> Self {
+        self.tangent_map = Some(tangent_map);
+        self
+    }
+
+    pub fn with_thin_film(mut self, thin_film: ThinFilm) -> Self {
+        self.thin_film = Some(thin_film);
+        self
+    }
+
+    fn fresnel(&self, record: &HitRecord, cosine: f64) -> Color {
+        if let Some(film) = &self.thin_film { return film.reflectance(record, cosine, 1.0, &self.eta, &self.k); }
+
+        microfacet::fresnel_conductor(cosine, &spectral_unbounded(record, &self.eta), &spectral_unbounded(record, &self.k))
+    }
+
+    fn evaluate_local(&self, record: &HitRecord, wo: &Vector3D, wi: &Vector3D) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return Color::default(); }
+
+        let wm = *wo + *wi;
+        if wm.near_zero() { return Color::default(); }
+        let wm = wm.normalized();
+
+        let fresnel = self.fresnel(record, Vector3D::dot(wo, &wm).abs());
+        fresnel * (self.distribution.d(&wm) * self.distribution.g(wo, wi) / (4.0 * wo.z() * wi.z()))
+    }
+
+    fn pdf_local(&self, wo: &Vector3D, wi: &Vector3D) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return 0.0; }
+
+        let wm = *wo + *wi;
+        if wm.near_zero() { return 0.0; }
+        let wm = wm.normalized();
+
+        self.distribution.pdf(wo, &wm) / (4.0 * Vector3D::dot(wo, &wm).abs())
+    }
+}
+
+impl Material for Conductor {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = tangent_frame(record, self.tangent_map.as_ref());
+        let wo = frame.to_local(&-ray_in.direction().normalized());
+        if wo.z() <= 0.0 { return None; }
+
+        if self.distribution.effectively_smooth() {
+            let direction = frame.to_world(&Vector3D::new(-wo.x(), -wo.y(), wo.z()));
+            *attenuation = self.fresnel(record, wo.z());
+
+            return geometric_side(ray_in, record, Ray::new(record.point, direction, ray_in.time()).with_differential(reflected_differential(ray_in, record, &direction)));
+        }
+
+        let wm = self.distribution.sample_wm(&wo);
+        let wi = microfacet::reflect(&wo, &wm);
+
+        let pdf = self.pdf_local(&wo, &wi);
+        if pdf <= 0.0 { return None; }
+
+        *attenuation = self.evaluate_local(record, &wo, &wi) * (wi.z() / pdf);
+        geometric_side(ray_in, record, Ray::new(record.point, frame.to_world(&wi), ray_in.time()))
+    }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = tangent_frame(record, self.tangent_map.as_ref());
+        self.evaluate_local(record, &frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()))
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let frame = tangent_frame(record, self.tangent_map.as_ref());
+        self.pdf_local(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()))
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { self.distribution.effectively_smooth() }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct RoughDielectric {
+    refractive_index: f64,
+    distribution: TrowbridgeReitz,
+    id: usize,
+}
+
+impl RoughDielectric {
+    pub fn new(refractive_index: f64, roughness: f64) -> Self { Self { id: next_material_id(), ..Self::lobe(refractive_index, roughness) } }
+
+    // A glass lobe built per hit inside another material, which owns the id, so it skips the shared id counter.
+    fn lobe(refractive_index: f64, roughness: f64) -> Self { Self { refractive_index, distribution: TrowbridgeReitz::from_roughness(roughness), id: 0 } }
+
+    // Relative index of the side the shading normal points away from.
+    fn eta(&self, record: &HitRecord) -> f64 { if record.front_face { self.refractive_index } else { 1.0 / self.refractive_index } }
+
+    fn half_vector(wo: &Vector3D, wi: &Vector3D, eta: f64) -> Option<(Vector3D, f64)> {
+        let (cos_theta_o, cos_theta_i) = (wo.z(), wi.z());
+        if cos_theta_o == 0.0 || cos_theta_i == 0.0 { return None; }
+
+        let etap = if cos_theta_o * cos_theta_i > 0.0 { 1.0 } else if cos_theta_o > 0.0 { eta } else { 1.0 / eta };
+        let wm = *wi * etap + *wo;
+        if wm.near_zero() { return None; }
+
+        let wm = wm.normalized();
+        let wm = if wm.z() < 0.0 { -wm } else { wm };
+
+        if Vector3D::dot(&wm, wi) * cos_theta_i < 0.0 || Vector3D::dot(&wm, wo) * cos_theta_o < 0.0 { return None; }
+        Some((wm, etap))
+    }
+
+    // Radiance is carried divided by eta squared, so transmission omits the usual 1 / etap^2 factor here.
+    fn evaluate_local(&self, wo: &Vector3D, wi: &Vector3D, eta: f64) -> f64 {
+        let (wm, etap) = match Self::half_vector(wo, wi, eta) {
+            Some(half) => half,
+            None => return 0.0,
+        };
+
+        let fresnel = microfacet::fresnel_dielectric(Vector3D::dot(wo, &wm), eta);
+        let microfacets = self.distribution.d(&wm) * self.distribution.g(wo, wi);
+
+        if wo.z() * wi.z() > 0.0 { return microfacets * fresnel / (4.0 * wo.z() * wi.z()).abs(); }
+
+        let denominator = (Vector3D::dot(wi, &wm) + Vector3D::dot(wo, &wm) / etap).powi(2) * wi.z() * wo.z();
+        microfacets * (1.0 - fresnel) * (Vector3D::dot(wi, &wm) * Vector3D::dot(wo, &wm) / denominator).abs()
+    }
+
+    fn pdf_local(&self, wo: &Vector3D, wi: &Vector3D, eta: f64) -> f64 {
+        let (wm, etap) = match Self::half_vector(wo, wi, eta) {
+            Some(half) => half,
+            None => return 0.0,
+        };
+
+        let fresnel = microfacet::fresnel_dielectric(Vector3D::dot(wo, &wm), eta);
+        let visible = self.distribution.pdf(wo, &wm);
+
+        if wo.z() * wi.z() > 0.0 { return visible / (4.0 * Vector3D::dot(wo, &wm).abs()) * fresnel; }
+
+        let denominator = (Vector3D::dot(wi, &wm) + Vector3D::dot(wo, &wm) / etap).powi(2);
+        visible * Vector3D::dot(wi, &wm).abs() / denominator * (1.0 - fresnel)
+    }
+
+    fn sample_local(&self, wo: &Vector3D, eta: f64) -> Option<Vector3D> {
+        let wm = self.distribution.sample_wm(wo);
+        let reflected = rand::thread_rng().gen_range(0.0..1.0) < microfacet::fresnel_dielectric(Vector3D::dot(wo, &wm), eta);
+
+        let wi = if reflected { microfacet::reflect(wo, &wm) } else { microfacet::refract(wo, &wm, eta)?.0 };
+        if (wo.z() * wi.z() > 0.0) != reflected { return None; }
+
+        Some(wi)
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = record.shading_frame();
+        let wo = frame.to_local(&-ray_in.direction().normalized());
+        let eta = self.eta(record);
+        let mut rng = rand::thread_rng();
+
+        if self.distribution.effectively_smooth() {
+            *attenuation = Color::new(1.0, 1.0, 1.0);
+            let normal = Vector3D::new(0.0, 0.0, 1.0);
+
+            if rng.gen_range(0.0..1.0) < microfacet::fresnel_dielectric(wo.z(), eta) {
+                let direction = frame.to_world(&microfacet::reflect(&wo, &normal));
+                return geometric_side(ray_in, record, Ray::new(record.point, direction, ray_in.time()).with_differential(reflected_differential(ray_in, record, &direction)));
+            }
+
+            let (wi, etap) = microfacet::refract(&wo, &normal, eta)?;
+            let direction = frame.to_world(&wi);
+            return geometric_side(ray_in, record, Ray::new(record.point, direction, ray_in.time()).with_differential(refracted_differential(ray_in, record, &direction, 1.0 / etap)));
+        }
+
+        let wi = self.sample_local(&wo, eta)?;
+        let pdf = self.pdf_local(&wo, &wi, eta);
+        if pdf <= 0.0 { return None; }
+
+        let value = self.evaluate_local(&wo, &wi, eta) * wi.z().abs() / pdf;
+        *attenuation = Color::new(value, value, value);
+
+        geometric_side(ray_in, record, Ray::new(record.point, frame.to_world(&wi), ray_in.time()))
+    }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = record.shading_frame();
+        let value = self.evaluate_local(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()), self.eta(record));
+
+        Color::new(value, value, value)
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let frame = record.shading_frame();
+        self.pdf_local(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()), self.eta(record))
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { self.distribution.effectively_smooth() }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 {
+        if Vector3D::dot(direction_out, &record.normal) >= 0.0 { return 1.0; }
+        (1.0 / self.eta(record)).powi(2)
+    }
+
+    fn id(&self) -> usize { self.id }
+}
+
+// Rotates the shading frame so its first axis follows a tangent-space direction encoded in the red and green channels.
+fn tangent_frame(record: &HitRecord, tangent_map: Option<&Arc<dyn Texture>>) -> Onb {
+    let frame = record.shading_frame();
+    let map = match tangent_map {
+        Some(map) => map,
+        None => return frame,
+    };
+
+    let encoded = texture_value(map, record) * 2.0 - Color::new(1.0, 1.0, 1.0);
+    let tangent = frame.u() * encoded.x() + frame.v() * encoded.y();
+    if tangent.near_zero() { return frame; }
+
+    let tangent = tangent.normalized();
+    Onb::from_axes(tangent, Vector3D::cross(&frame.w(), &tangent), frame.w())
+}
+
+fn scalar_value(texture: &Arc<dyn Texture>, record: &HitRecord) -> f64 {
+    let value = texture_value(texture, record);
+    (value.x() + value.y() + value.z()) / 3.0
+}
+
+fn constant(value: f64) -> Arc<dyn Texture> { Arc::new(SolidColor::new(Color::new(value, value, value))) }
+
+fn schlick_weight(cosine: f64) -> f64 { (1.0 - cosine.clamp(0.0, 1.0)).powi(5) }
+
+fn schlick_fresnel(f0: &Color, cosine: f64) -> Color { *f0 + (Color::new(1.0, 1.0, 1.0) - *f0) * schlick_weight(cosine) }
+
+fn cosine_hemisphere() -> Vector3D {
+    let disk = Vector3D::random_in_unit_disk();
+    Vector3D::new(disk.x(), disk.y(), (1.0 - disk.length_squared()).max(0.0).sqrt())
+}
+
+#[derive(Clone)]
+pub struct Principled {
+    base_color: Arc<dyn Texture>,
+    metallic: Arc<dyn Texture>,
+    roughness: Arc<dyn Texture>,
+    specular: Arc<dyn Texture>,
+    specular_tint: Arc<dyn Texture>,
+    sheen: Arc<dyn Texture>,
+    clearcoat: Arc<dyn Texture>,
+    clearcoat_roughness: Arc<dyn Texture>,
+    transmission: Arc<dyn Texture>,
+    refractive_index: f64,
+    id: usize,
+}
+
+impl Principled {
+    pub fn new(base_color: Color) -> Self { Self::from_texture(Arc::new(SolidColor::new(base_color))) }
+
+    pub fn from_texture(base_color: Arc<dyn Texture>) -> Self {
+        Self {
+            base_color,
+            metallic: constant(0.0),
+            roughness: constant(0.5),
+            specular: constant(0.5),
+            specular_tint: constant(0.0),
+            sheen: constant(0.0),
+            clearcoat: constant(0.0),
+            clearcoat_roughness: constant(0.1),
+            transmission: constant(0.0),
+            refractive_index: 1.5,
+            id: next_material_id(),
+        }
+    }
+
+    pub fn from_gltf(material: &GltfMaterial) -> Self {
+        let base_color: Arc<dyn Texture> = match &material.base_color_texture {
+            Some(texture) => Arc::new(ScaleTexture::new(texture.clone(), material.base_color_factor)),
+            None => Arc::new(SolidColor::new(material.base_color_factor)),
+        };
+
+        let packed = |channel: usize, factor: f64| -> Arc<dyn Texture> {
+            match &material.metallic_roughness_texture {
+                Some(texture) => Arc::new(ScaleTexture::new(Arc::new(ChannelTexture::new(texture.clone(), channel)), Color::new(factor, factor, factor))),
+                None => constant(factor),
+            }
+        };
+
+        // glTF dielectrics reflect F0 = ((ior - 1) / (ior + 1))^2 scaled by KHR_materials_specular; specular 0.5 maps to F0 = 0.04.
+        let f0 = ((material.ior - 1.0) / (material.ior + 1.0)).powi(2) * material.specular_factor;
+
+        Self {
+            metallic: packed(2, material.metallic_factor),
+            roughness: packed(1, material.roughness_factor),
+            specular: constant((f0 / 0.08).min(1.0)),
+            sheen: constant(material.sheen_factor),
+            clearcoat: constant(material.clearcoat_factor),
+            clearcoat_roughness: constant(material.clearcoat_roughness_factor),
+            transmission: constant(material.transmission_factor),
+            refractive_index: material.ior,
+            ..Self::from_texture(base_color)
+        }
+    }
+
+    pub fn from_mtl(material: &MtlMaterial) -> Self {
+        let base_color: Arc<dyn Texture> = match &material.diffuse_map {
+            Some(texture) => Arc::new(ScaleTexture::new(texture.clone(), material.diffuse)),
+            None => Arc::new(SolidColor::new(material.diffuse)),
+        };
+
+        // Without a PBR roughness, convert the Phong exponent through the Blinn-Phong to GGX alpha relation.
+        let roughness = material.roughness.unwrap_or_else(|| (2.0 / (material.shininess.max(0.0) + 2.0)).powf(0.25));
+        let scalar = |map: &Option<Arc<dyn Texture>>, value: f64| map.clone().unwrap_or_else(|| constant(value));
+        let f0 = ((material.refractive_index - 1.0) / (material.refractive_index + 1.0)).powi(2);
+
+        Self {
+            metallic: scalar(&material.metallic_map, material.metallic.unwrap_or(0.0)),
+            roughness: scalar(&material.roughness_map, roughness),
+            specular: constant((f0 / 0.08).min(1.0)),
+            sheen: constant(material.sheen),
+            clearcoat: constant(material.clearcoat),
+            clearcoat_roughness: constant(material.clearcoat_roughness),
+            transmission: constant((1.0 - material.dissolve).clamp(0.0, 1.0)),
+            refractive_index: material.refractive_index,
+            ..Self::from_texture(base_color)
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: Arc<dyn Texture>) -> Self {
+        self.metallic = metallic;
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Arc<dyn Texture>) -> Self {
+        self.roughness = roughness;
+        self
+    }
+
+    pub fn with_specular(mut self, specular: Arc<dyn Texture>, specular_tint: Arc<dyn Texture>) -> Self {
+        self.specular = specular;
+        self.specular_tint = specular_tint;
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: Arc<dyn Texture>) -> Self {
+        self.sheen = sheen;
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: Arc<dyn Texture>, roughness: Arc<dyn Texture>) -> Self {
+        self.clearcoat = clearcoat;
+        self.clearcoat_roughness = roughness;
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: Arc<dyn Texture>, refractive_index: f64) -> Self {
+        self.transmission = transmission;
+        self.refractive_index = refractive_index;
+        self
+    }
+
+    fn lobes(&self, record: &HitRecord) -> PrincipledLobes {
+        let base_color = texture_value(&self.base_color, record);
+        let metallic = scalar_value(&self.metallic, record).clamp(0.0, 1.0);
+        let roughness = scalar_value(&self.roughness, record).clamp(0.02, 1.0);
+        let specular = scalar_value(&self.specular, record).max(0.0);
+        let specular_tint = scalar_value(&self.specular_tint, record).clamp(0.0, 1.0);
+        let sheen = scalar_value(&self.sheen, record).max(0.0);
+        let clearcoat = scalar_value(&self.clearcoat, record).max(0.0);
+        let clearcoat_roughness = scalar_value(&self.clearcoat_roughness, record).clamp(0.02, 1.0);
+        let transmission = scalar_value(&self.transmission, record).clamp(0.0, 1.0);
+
+        let luminance = color::average(&base_color);
+        let tint = if luminance > 0.0 { base_color / luminance } else { Color::new(1.0, 1.0, 1.0) };
+        let white = Color::new(1.0, 1.0, 1.0);
+
+        let dielectric_specular = (white * (1.0 - specular_tint) + tint * specular_tint) * (0.08 * specular);
+        let eta = if record.front_face { self.refractive_index } else { 1.0 / self.refractive_index };
+
+        PrincipledLobes {
+            base_color: spectral_reflectance(record, &base_color),
+            specular_color: spectral_unbounded(record, &(dielectric_specular * (1.0 - metallic) + base_color * metallic)),
+            sheen_color: spectral_unbounded(record, &((white + tint) * (0.5 * sheen))),
+            roughness,
+            diffuse_weight: (1.0 - metallic) * (1.0 - transmission),
+            specular_weight: 1.0 - (1.0 - metallic) * transmission,
+            glass_weight: (1.0 - metallic) * transmission,
+            clearcoat: 0.25 * clearcoat,
+            specular: TrowbridgeReitz::from_roughness(roughness),
+            coat: TrowbridgeReitz::from_roughness(clearcoat_roughness),
+            glass: RoughDielectric::lobe(self.refractive_index, roughness),
+            eta,
+        }
+    }
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = record.shading_frame();
+        let wo = frame.to_local(&-ray_in.direction().normalized());
+        let lobes = self.lobes(record);
+
+        let wi = lobes.sample(&wo)?;
+        let pdf = lobes.pdf(&wo, &wi);
+        if pdf <= 0.0 { return None; }
+
+        *attenuation = lobes.evaluate(&wo, &wi) * (wi.z().abs() / pdf);
+        geometric_side(ray_in, record, Ray::new(record.point, frame.to_world(&wi), ray_in.time()))
+    }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = record.shading_frame();
+        self.lobes(record).evaluate(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()))
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let frame = record.shading_frame();
+        self.lobes(record).pdf(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()))
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 {
+        if Vector3D::dot(direction_out, &record.normal) >= 0.0 { return 1.0; }
+
+        let eta = if record.front_face { self.refractive_index } else { 1.0 / self.refractive_index };
+        (1.0 / eta).powi(2)
+    }
+
+    fn id(&self) -> usize { self.id }
+}
+
+// Parameters of a glTF 2.0 metallic-roughness material, including the common KHR material extensions.
+#[derive(Clone)]
+pub struct GltfMaterial {
+    pub base_color_factor: Color,
+    pub base_color_texture: Option<Arc<dyn Texture>>,
+    pub metallic_factor: f64,
+    pub roughness_factor: f64,
+    pub metallic_roughness_texture: Option<Arc<dyn Texture>>,
+    pub specular_factor: f64,
+    pub sheen_factor: f64,
+    pub clearcoat_factor: f64,
+    pub clearcoat_roughness_factor: f64,
+    pub transmission_factor: f64,
+    pub ior: f64,
+}
+
+impl Default for GltfMaterial {
+    fn default() -> Self {
+        Self {
+            base_color_factor: Color::new(1.0, 1.0, 1.0),
+            base_color_texture: None,
+            metallic_factor: 1.0,
+            roughness_factor: 1.0,
+            metallic_roughness_texture: None,
+            specular_factor: 1.0,
+            sheen_factor: 0.0,
+            clearcoat_factor: 0.0,
+            clearcoat_roughness_factor: 0.0,
+            transmission_factor: 0.0,
+            ior: 1.5,
+        }
+    }
+}
+
+// Wavefront MTL statements: Kd/map_Kd, Ns, Ni, d, and the PBR extension Pr/map_Pr, Pm/map_Pm, Ps, Pc and Pcr.
+#[derive(Clone)]
+pub struct MtlMaterial {
+    pub diffuse: Color,
+    pub diffuse_map: Option<Arc<dyn Texture>>,
+    pub shininess: f64,
+    pub refractive_index: f64,
+    pub dissolve: f64,
+    pub roughness: Option<f64>,
+    pub roughness_map: Option<Arc<dyn Texture>>,
+    pub metallic: Option<f64>,
+    pub metallic_map: Option<Arc<dyn Texture>>,
+    pub sheen: f64,
+    pub clearcoat: f64,
+    pub clearcoat_roughness: f64,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        Self {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            diffuse_map: None,
+            shininess: 0.0,
+            refractive_index: 1.5,
+            dissolve: 1.0,
+            roughness: None,
+            roughness_map: None,
+            metallic: None,
+            metallic_map: None,
+            sheen: 0.0,
+            clearcoat: 0.0,
+            clearcoat_roughness: 0.1,
+        }
+    }
+}
+
+struct PrincipledLobes {
+    base_color: Color,
+    specular_color: Color,
+    sheen_color: Color,
+    roughness: f64,
+    diffuse_weight: f64,
+    specular_weight: f64,
+    glass_weight: f64,
+    clearcoat: f64,
+    specular: TrowbridgeReitz,
+    coat: TrowbridgeReitz,
+    glass: RoughDielectric,
+    eta: f64,
+}
+
+impl PrincipledLobes {
+    // Selection probabilities for the diffuse, specular, glass and clearcoat lobes, estimated from their albedos.
+    fn probabilities(&self, wo: &Vector3D) -> [f64; 4] {
+        let reflecting = wo.z() > 0.0;
+        let mut weights = [
+            if reflecting { self.diffuse_weight * color::average(&(self.base_color + self.sheen_color)) } else { 0.0 },
+            if reflecting { self.specular_weight * color::average(&schlick_fresnel(&self.specular_color, wo.z())) } else { 0.0 },
+            self.glass_weight,
+            if reflecting { self.clearcoat * (0.04 + 0.96 * schlick_weight(wo.z())) } else { 0.0 },
+        ];
+
+        let total: f64 = weights.iter().sum();
+        if total <= 0.0 { return [0.0; 4]; }
+
+        for weight in weights.iter_mut() { *weight /= total; }
+        weights
+    }
+
+    fn evaluate(&self, wo: &Vector3D, wi: &Vector3D) -> Color {
+        let mut value = Color::default();
+
+        if self.glass_weight > 0.0 {
+            let glass = self.glass.evaluate_local(wo, wi, self.eta) * self.glass_weight;
+            value += if wo.z() * wi.z() < 0.0 { self.base_color * glass } else { Color::new(glass, glass, glass) };
+        }
+
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return value; }
+
+        let wm = (*wo + *wi).normalized();
+        let cosine_d = Vector3D::dot(wi, &wm);
+
+        if self.diffuse_weight > 0.0 {
+            let (fresnel_in, fresnel_out) = (schlick_weight(wi.z()), schlick_weight(wo.z()));
+            let retro = 2.0 * self.roughness * cosine_d.powi(2);
+
+            let lambert = (1.0 - 0.5 * fresnel_in) * (1.0 - 0.5 * fresnel_out);
+            let retro_reflection = retro * (fresnel_in + fresnel_out + fresnel_in * fresnel_out * (retro - 1.0));
+
+            let diffuse = self.base_color * ((lambert + retro_reflection) / PI) + self.sheen_color * schlick_weight(cosine_d);
+            value += diffuse * self.diffuse_weight;
+        }
+
+        if self.specular_weight > 0.0 {
+            let microfacets = self.specular.d(&wm) * self.specular.g(wo, wi) / (4.0 * wo.z() * wi.z());
+            value += schlick_fresnel(&self.specular_color, Vector3D::dot(wo, &wm)) * (microfacets * self.specular_weight);
+        }
+
+        if self.clearcoat > 0.0 {
+            let microfacets = self.coat.d(&wm) * self.coat.g(wo, wi) / (4.0 * wo.z() * wi.z());
+            let fresnel = 0.04 + 0.96 * schlick_weight(Vector3D::dot(wo, &wm));
+            value += Color::new(1.0, 1.0, 1.0) * (microfacets * fresnel * self.clearcoat);
+        }
+
+        value
+    }
+
+    fn pdf(&self, wo: &Vector3D, wi: &Vector3D) -> f64 {
+        let [diffuse, specular, glass, clearcoat] = self.probabilities(wo);
+        let mut pdf = if glass > 0.0 { glass * self.glass.pdf_local(wo, wi, self.eta) } else { 0.0 };
+
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return pdf; }
+
+        let wm = (*wo + *wi).normalized();
+        let reflection = 4.0 * Vector3D::dot(wo, &wm).abs();
+
+        pdf += diffuse * wi.z() / PI;
+        if specular > 0.0 { pdf += specular * self.specular.pdf(wo, &wm) / reflection; }
+        if clearcoat > 0.0 { pdf += clearcoat * self.coat.pdf(wo, &wm) / reflection; }
+
+        pdf
+    }
+
+    fn sample(&self, wo: &Vector3D) -> Option<Vector3D> {
+        let probabilities = self.probabilities(wo);
+        let mut choice = rand::thread_rng().gen_range(0.0..1.0);
+
+        let lobe = probabilities.iter().position(|probability| {
+            choice -= probability;
+            choice < 0.0
+        })?;
+
+        let wi = match lobe {
+            0 => cosine_hemisphere(),
+            1 => microfacet::reflect(wo, &self.specular.sample_wm(wo)),
+            2 => self.glass.sample_local(wo, self.eta)?,
+            _ => microfacet::reflect(wo, &self.coat.sample_wm(wo)),
+        };
+
+        if lobe != 2 && wi.z() <= 0.0 { return None; }
+        Some(wi)
+    }
+}
+
+#[derive(Clone)]
+pub struct DiffuseLight {
+    emit: Arc<dyn Texture>,
+    id: usize,
+}
+
+impl DiffuseLight {
+    pub fn new(emit: Color) -> Self { Self::from_texture(Arc::new(SolidColor::new(emit))) }
+
+    pub fn from_texture(emit: Arc<dyn Texture>) -> Self { Self { emit, id: next_material_id() } }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray_in: &Ray, _record: &HitRecord, _attenuation: &mut Color) -> Option<Ray> {
+        None
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color { spectral_unbounded(record, &texture_value(&self.emit, record)) }
+
+    fn is_emissive(&self) -> bool { true }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
+pub struct NormalMap {
+    material: Arc<dyn Material>,
+    map: Arc<dyn Texture>,
+    strength: f64,
+    id: usize,
+}
+
+impl NormalMap {
+    pub fn new(material: Arc<dyn Material>, map: Arc<dyn Texture>, strength: f64) -> Self { Self { material, map, strength, id: next_material_id() } }
+}
+
+impl Material for NormalMap {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> { self.material.scatter(ray_in, record, attenuation) }
+
+    fn emitted(&self, record: &HitRecord) -> Color { self.material.emitted(record) }
+
+    fn is_emissive(&self) -> bool { self.material.is_emissive() }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        self.material.evaluate(record, direction_in, direction_out)
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        self.material.scattering_pdf(record, direction_in, direction_out)
+    }
+
+    fn is_specular(&self, record: &HitRecord) -> bool { self.material.is_specular(record) }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 { self.material.adjoint_correction(record, direction_out) }
+
+    fn shading_normal(&self, record: &HitRecord) -> Option<Vector3D> {
+        let encoded = texture_value(&self.map, record) * 2.0 - Color::new(1.0, 1.0, 1.0);
+        let tangent = Vector3D::new(encoded.x() * self.strength, encoded.y() * self.strength, encoded.z());
+
+        let base = self.material.shading_normal(record).map_or(record.shading_frame(), |normal| {
+            let mut shaded = record.clone();
+            shaded.normal = normal;
+            shaded.shading_frame()
+        });
+
+        Some(base.to_world(&tangent))
+    }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
+pub struct BumpMap {
+    material: Arc<dyn Material>,
+    height: Arc<dyn Texture>,
+    scale: f64,
+    id: usize,
+}
+
+impl BumpMap {
+    pub fn new(material: Arc<dyn Material>, height: Arc<dyn Texture>, scale: f64) -> Self { Self { material, height, scale, id: next_material_id() } }
+
+    fn height_at(&self, record: &HitRecord, du: f64, dv: f64) -> f64 {
+        let point = record.point + record.dpdu * du + record.dpdv * dv;
+        let value = self.height.value(record.u + du, record.v + dv, &point, record.time);
+
+        self.scale * (value.x() + value.y() + value.z()) / 3.0
+    }
+}
+
+impl Material for BumpMap {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> { self.material.scatter(ray_in, record, attenuation) }
+
+    fn emitted(&self, record: &HitRecord) -> Color { self.material.emitted(record) }
+
+    fn is_emissive(&self) -> bool { self.material.is_emissive() }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        self.material.evaluate(record, direction_in, direction_out)
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        self.material.scattering_pdf(record, direction_in, direction_out)
+    }
+
+    fn is_specular(&self, record: &HitRecord) -> bool { self.material.is_specular(record) }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 { self.material.adjoint_correction(record, direction_out) }
+
+    fn shading_normal(&self, record: &HitRecord) -> Option<Vector3D> {
+        if record.dpdu.near_zero() || record.dpdv.near_zero() { return None; }
+
+        let normal = self.material.shading_normal(record).unwrap_or(record.normal).normalized();
+        let outward = Vector3D::cross(&record.dpdu, &record.dpdv);
+        let normal = if Vector3D::dot(&outward, &normal) < 0.0 { -normal } else { normal };
+
+        let delta = BUMP_DELTA;
+        let height = self.height_at(record, 0.0, 0.0);
+        let slope_u = (self.height_at(record, delta, 0.0) - height) / delta;
+        let slope_v = (self.height_at(record, 0.0, delta) - height) / delta;
+
+        let dpdu = record.dpdu + normal * slope_u;
+        let dpdv = record.dpdv + normal * slope_v;
+
+        Some(Vector3D::cross(&dpdu, &dpdv))
+    }
+
+    fn id(&self) -> usize { self.id }
+}
+
+// Reweights a direction sampled by one lobe of a composite material with the composite's own value and density.
+fn importance_weight(material: &dyn Material, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Option<Color> {
+    let pdf = material.scattering_pdf(record, direction_in, direction_out);
+    if pdf <= 0.0 { return None; }
+
+    let cosine = Vector3D::dot(&direction_out.normalized(), &record.normal).abs();
+    Some(material.evaluate(record, direction_in, direction_out) * (cosine / pdf))
+}
+
+#[derive(Clone)]
+pub struct Blend {
+    first: Arc<dyn Material>,
+    second: Arc<dyn Material>,
+    mask: Arc<dyn Texture>,
+    id: usize,
+}
+
+impl Blend {
+    pub fn new(first: Arc<dyn Material>, second: Arc<dyn Material>, weight: f64) -> Self { Self::from_texture(first, second, constant(weight)) }
+
+    // The mask selects the second material where it is white and the first where it is black.
+    pub fn from_texture(first: Arc<dyn Material>, second: Arc<dyn Material>, mask: Arc<dyn Texture>) -> Self { Self { first, second, mask, id: next_material_id() } }
+
+    fn weight(&self, record: &HitRecord) -> f64 { scalar_value(&self.mask, record).clamp(0.0, 1.0) }
+}
+
+impl Material for Blend {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let chosen = if rand::thread_rng().gen_range(0.0..1.0) < self.weight(record) { &self.second } else { &self.first };
+        let scattered = chosen.scatter(ray_in, record, attenuation)?;
+        if self.is_specular(record) { return Some(scattered); }
+
+        *attenuation = importance_weight(self, record, &ray_in.direction().normalized(), &scattered.direction())?;
+        Some(scattered)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color {
+        let weight = self.weight(record);
+        self.first.emitted(record) * (1.0 - weight) + self.second.emitted(record) * weight
+    }
+
+    fn is_emissive(&self) -> bool { self.first.is_emissive() || self.second.is_emissive() }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let weight = self.weight(record);
+        self.first.evaluate(record, direction_in, direction_out) * (1.0 - weight) + self.second.evaluate(record, direction_in, direction_out) * weight
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let weight = self.weight(record);
+        self.first.scattering_pdf(record, direction_in, direction_out) * (1.0 - weight) + self.second.scattering_pdf(record, direction_in, direction_out) * weight
+    }
+
+    // Only the lobes the mask mixes at this point count. A delta lobe mixed with a smooth one is followed by scattering, where each
+    // lobe's own attenuation already carries the right weight.
+    fn is_specular(&self, record: &HitRecord) -> bool {
+        match self.weight(record) {
+            weight if weight <= 0.0 => self.first.is_specular(record),
+            weight if weight >= 1.0 => self.second.is_specular(record),
+            _ => self.first.is_specular(record) || self.second.is_specular(record),
+        }
+    }
+
+    // Only transmissive materials produce a correction other than one, so prefer whichever side does.
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 {
+        let (first, second) = (self.first.adjoint_correction(record, direction_out), self.second.adjoint_correction(record, direction_out));
+
+        if first == 1.0 { return second; }
+        if second == 1.0 { return first; }
+
+        let weight = self.weight(record);
+        first * (1.0 - weight) + second * weight
+    }
+
+    fn shading_normal(&self, record: &HitRecord) -> Option<Vector3D> { self.first.shading_normal(record).or_else(|| self.second.shading_normal(record)) }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
+pub struct Coated {
+    base: Arc<dyn Material>,
+    refractive_index: f64,
+    distribution: TrowbridgeReitz,
+    absorption: Color,
+    thickness: f64,
+    id: usize,
+}
+
+impl Coated {
+    pub fn new(base: Arc<dyn Material>, refractive_index: f64, roughness: f64) -> Self {
+        Self {
+            base,
+            refractive_index,
+            distribution: TrowbridgeReitz::from_roughness(roughness.max(0.02)),
+            absorption: Color::default(),
+            thickness: 0.0,
+            id: next_material_id(),
+        }
+    }
+
+    pub fn with_absorption(mut self, absorption: Color, thickness: f64) -> Self {
+        self.absorption = absorption;
+        self.thickness = thickness;
+        self
+    }
+
+    fn coat(&self, wo: &Vector3D, wi: &Vector3D) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return 0.0; }
+
+        let wm = (*wo + *wi).normalized();
+        let fresnel = microfacet::fresnel_dielectric(Vector3D::dot(wo, &wm), self.refractive_index);
+
+        self.distribution.d(&wm) * self.distribution.g(wo, wi) * fresnel / (4.0 * wo.z() * wi.z())
+    }
+
+    fn coat_pdf(&self, wo: &Vector3D, wi: &Vector3D) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return 0.0; }
+
+        let wm = (*wo + *wi).normalized();
+        self.distribution.pdf(wo, &wm) / (4.0 * Vector3D::dot(wo, &wm).abs())
+    }
+
+    // Light reaching the base crosses the coat interface twice and the absorbing layer along both directions.
+    fn transmittance(&self, record: &HitRecord, wo: &Vector3D, wi: &Vector3D) -> Color {
+        let (cosine_out, cosine_in) = (wo.z().abs().max(1e-4), wi.z().abs().max(1e-4));
+        let fresnel = (1.0 - microfacet::fresnel_dielectric(cosine_out, self.refractive_index)) * (1.0 - microfacet::fresnel_dielectric(cosine_in, self.refractive_index));
+        color::transmittance(&spectral_unbounded(record, &self.absorption), self.thickness * (1.0 / cosine_out + 1.0 / cosine_in)) * fresnel
+    }
+
+    // Sample the coat at least a quarter of the time so sharp highlights over dark bases still converge.
+    fn coat_probability(&self, wo: &Vector3D) -> f64 {
+        if wo.z() <= 0.0 { return 0.0; }
+        microfacet::fresnel_dielectric(wo.z(), self.refractive_index).max(0.25)
+    }
+}
+
+impl Material for Coated {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = record.shading_frame();
+        let direction_in = ray_in.direction().normalized();
+        let wo = frame.to_local(&-direction_in);
+        let probability = self.coat_probability(&wo);
+
+        if rand::thread_rng().gen_range(0.0..1.0) < probability {
+            let wi = microfacet::reflect(&wo, &self.distribution.sample_wm(&wo));
+            if wi.z() <= 0.0 { return None; }
+
+            let scattered = Ray::new(record.point, frame.to_world(&wi), ray_in.time());
+            *attenuation = if self.base.is_specular(record) {
+                Color::new(1.0, 1.0, 1.0) * (self.coat(&wo, &wi) * wi.z() / (probability * self.coat_pdf(&wo, &wi)))
+            } else {
+                importance_weight(self, record, &direction_in, &scattered.direction())?
+            };
+
+            return geometric_side(ray_in, record, scattered);
+        }
+
+        let scattered = self.base.scatter(ray_in, record, attenuation)?;
+        *attenuation = if self.base.is_specular(record) {
+            *attenuation * self.transmittance(record, &wo, &frame.to_local(&scattered.direction().normalized())) / (1.0 - probability)
         } else {
-            Vector3D::refract(&direction_normal, record_normal, refraction_ratio)
+            importance_weight(self, record, &direction_in, &scattered.direction())?
         };
 
-        Some(Ray::new(record_point.clone(), direction, ray_in.time()))
+        Some(scattered)
+    }
+
+    fn emitted(&self, record: &HitRecord) -> Color { self.base.emitted(record) }
+
+    fn is_emissive(&self) -> bool { self.base.is_emissive() }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = record.shading_frame();
+        let (wo, wi) = (frame.to_local(&-direction_in.normalized()), frame.to_local(&direction_out.normalized()));
+        if wo.z() <= 0.0 { return self.base.evaluate(record, direction_in, direction_out); }
+
+        let coat = self.coat(&wo, &wi);
+        Color::new(coat, coat, coat) + self.base.evaluate(record, direction_in, direction_out) * self.transmittance(record, &wo, &wi)
     }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let frame = record.shading_frame();
+        let (wo, wi) = (frame.to_local(&-direction_in.normalized()), frame.to_local(&direction_out.normalized()));
+        let probability = self.coat_probability(&wo);
+
+        probability * self.coat_pdf(&wo, &wi) + (1.0 - probability) * self.base.scattering_pdf(record, direction_in, direction_out)
+    }
+
+    fn is_specular(&self, record: &HitRecord) -> bool { self.base.is_specular(record) }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 { self.base.adjoint_correction(record, direction_out) }
+
+    fn shading_normal(&self, record: &HitRecord) -> Option<Vector3D> { self.base.shading_normal(record) }
+
+    fn id(&self) -> usize { self.id }
+}
+
+#[derive(Clone)]
+pub struct TwoSided {
+    front: Arc<dyn Material>,
+    back: Arc<dyn Material>,
+    id: usize,
+}
+
+impl TwoSided {
+    pub fn new(front: Arc<dyn Material>, back: Arc<dyn Material>) -> Self { Self { front, back, id: next_material_id() } }
+
+    fn side(&self, record: &HitRecord) -> &Arc<dyn Material> { if record.front_face { &self.front } else { &self.back } }
+}
+
+impl Material for TwoSided {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> { self.side(record).scatter(ray_in, record, attenuation) }
+
+    fn emitted(&self, record: &HitRecord) -> Color { self.side(record).emitted(record) }
+
+    fn is_emissive(&self) -> bool { self.front.is_emissive() || self.back.is_emissive() }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        self.side(record).evaluate(record, direction_in, direction_out)
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        self.side(record).scattering_pdf(record, direction_in, direction_out)
+    }
+
+    fn is_specular(&self, record: &HitRecord) -> bool { self.side(record).is_specular(record) }
+
+    fn adjoint_correction(&self, record: &HitRecord, direction_out: &Vector3D) -> f64 { self.side(record).adjoint_correction(record, direction_out) }
+
+    fn shading_normal(&self, record: &HitRecord) -> Option<Vector3D> { self.side(record).shading_normal(record) }
+
+    fn id(&self) -> usize { self.id }
 }
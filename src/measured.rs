@@ -0,0 +1,234 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::color::{self, Color};
+use crate::hittable::HitRecord;
+use crate::material::{self, Material};
+use crate::microfacet::{self, TrowbridgeReitz};
+use crate::ray::Ray;
+use crate::vector::Vector3D;
+
+const THETA_HALF_RESOLUTION: usize = 90;
+const THETA_DIFFERENCE_RESOLUTION: usize = 90;
+const PHI_DIFFERENCE_RESOLUTION: usize = 180;
+const SAMPLES: usize = THETA_HALF_RESOLUTION * THETA_DIFFERENCE_RESOLUTION * PHI_DIFFERENCE_RESOLUTION;
+const CHANNEL_SCALE: [f64; 3] = [1.0 / 1500.0, 1.15 / 1500.0, 1.66 / 1500.0];
+
+// Isotropic BRDF tabulated over half and difference angles in the MERL layout.
+pub struct MeasuredBrdf {
+    values: Vec<f32>,
+    distribution: TrowbridgeReitz,
+    specular_probability: f64,
+    id: usize,
+}
+
+impl MeasuredBrdf {
+    // MERL .binary: three little-endian i32 dimensions (90, 90, 180) followed by red, green and blue blocks of f64 values.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        let bytes = fs::read(path)?;
+        if bytes.len() < 12 { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "measured BRDF file is truncated")); }
+
+        let dimensions: Vec<usize> = bytes[0..12].chunks_exact(4).map(|chunk| i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as usize).collect();
+        if dimensions != [THETA_HALF_RESOLUTION, THETA_DIFFERENCE_RESOLUTION, PHI_DIFFERENCE_RESOLUTION] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "unexpected measured BRDF dimensions"));
+        }
+
+        let end = 12 + 3 * SAMPLES * 8;
+        if bytes.len() < end { return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "measured BRDF file is truncated")); }
+
+        let values = bytes[12..end]
+            .chunks_exact(8)
+            .enumerate()
+            .map(|(index, chunk)| {
+                let value = f64::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3], chunk[4], chunk[5], chunk[6], chunk[7]]);
+                (value.max(0.0) * CHANNEL_SCALE[index / SAMPLES]) as f32
+            })
+            .collect();
+
+        Ok(Self::from_values(values))
+    }
+
+    fn from_values(values: Vec<f32>) -> Self {
+        let mut brdf = Self { values, distribution: TrowbridgeReitz::new(1.0, 1.0), specular_probability: 0.0, id: material::next_material_id() };
+        brdf.fit_sampling();
+        brdf
+    }
+
+    // Fit a GGX lobe over a diffuse floor to the retro-reflection profile so sampling roughly follows the data.
+    fn fit_sampling(&mut self) {
+        let profile: Vec<f64> = (0..THETA_HALF_RESOLUTION).map(|index| color::average(&self.lookup(index, 0, 0))).collect();
+        let floor = profile.iter().cloned().fold(f64::INFINITY, f64::min);
+        let peak = profile[0] - floor;
+        if peak <= 0.0 { return; }
+
+        let half_width = profile.iter().position(|value| value - floor < peak / 2.0).unwrap_or(THETA_HALF_RESOLUTION - 1);
+        let theta = (half_width as f64 / THETA_HALF_RESOLUTION as f64).powi(2) * PI / 2.0;
+        let alpha = (theta.tan() / (2.0f64.sqrt() - 1.0).sqrt()).clamp(0.02, 1.0);
+
+        let specular = peak * 4.0 * PI * alpha * alpha;
+        let diffuse = floor * PI;
+
+        self.distribution = TrowbridgeReitz::new(alpha, alpha);
+        self.specular_probability = (specular / (specular + diffuse)).clamp(0.1, 0.9);
+    }
+
+    fn lookup(&self, theta_half: usize, theta_difference: usize, phi_difference: usize) -> Color {
+        let index = phi_difference + PHI_DIFFERENCE_RESOLUTION * (theta_difference + THETA_DIFFERENCE_RESOLUTION * theta_half);
+        Color::new(self.values[index] as f64, self.values[index + SAMPLES] as f64, self.values[index + 2 * SAMPLES] as f64)
+    }
+
+    fn evaluate_local(&self, wo: &Vector3D, wi: &Vector3D) -> Color {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return Color::default(); }
+
+        let half = *wo + *wi;
+        if half.near_zero() { return Color::default(); }
+        let half = half.normalized();
+
+        let theta_half = half.z().clamp(-1.0, 1.0).acos();
+        let phi_half = half.y().atan2(half.x());
+
+        let difference = rotate(&rotate(wi, &Vector3D::new(0.0, 0.0, 1.0), -phi_half), &Vector3D::new(0.0, 1.0, 0.0), -theta_half);
+        let theta_difference = difference.z().clamp(-1.0, 1.0).acos();
+        let mut phi_difference = difference.y().atan2(difference.x());
+
+        // Reciprocity makes the table symmetric under a half turn of the difference azimuth.
+        if phi_difference < 0.0 { phi_difference += PI; }
+
+        let half_index = (THETA_HALF_RESOLUTION as f64 * (theta_half / (PI / 2.0)).sqrt()) as usize;
+        let difference_index = (theta_difference / (PI / 2.0) * THETA_DIFFERENCE_RESOLUTION as f64) as usize;
+        let phi_index = (phi_difference / PI * PHI_DIFFERENCE_RESOLUTION as f64) as usize;
+
+        self.lookup(
+            half_index.min(THETA_HALF_RESOLUTION - 1),
+            difference_index.min(THETA_DIFFERENCE_RESOLUTION - 1),
+            phi_index.min(PHI_DIFFERENCE_RESOLUTION - 1),
+        )
+    }
+
+    fn pdf_local(&self, wo: &Vector3D, wi: &Vector3D) -> f64 {
+        if wo.z() <= 0.0 || wi.z() <= 0.0 { return 0.0; }
+
+        let half = (*wo + *wi).normalized();
+        let specular = self.distribution.pdf(wo, &half) / (4.0 * Vector3D::dot(wo, &half).abs());
+
+        self.specular_probability * specular + (1.0 - self.specular_probability) * wi.z() / PI
+    }
+
+    fn sample_local(&self, wo: &Vector3D) -> Option<Vector3D> {
+        let mut rng = rand::thread_rng();
+
+        let wi = if rng.gen_range(0.0..1.0) < self.specular_probability {
+            microfacet::reflect(wo, &self.distribution.sample_wm(wo))
+        } else {
+            let (u, v) = (rng.gen_range(0.0..1.0f64), rng.gen_range(0.0..1.0f64));
+            let (radius, phi) = (u.sqrt(), 2.0 * PI * v);
+            Vector3D::new(radius * phi.cos(), radius * phi.sin(), (1.0 - u).max(0.0).sqrt())
+        };
+
+        if wi.z() > 0.0 { Some(wi) } else { None }
+    }
+
+    fn spectral(record: &HitRecord, value: Color) -> Color {
+        match &record.wavelengths {
+            Some(wavelengths) => wavelengths.unbounded(&value),
+            None => value,
+        }
+    }
+}
+
+impl Material for MeasuredBrdf {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let frame = record.shading_frame();
+        let wo = frame.to_local(&-ray_in.direction().normalized());
+        if wo.z() <= 0.0 { return None; }
+
+        let wi = self.sample_local(&wo)?;
+        let pdf = self.pdf_local(&wo, &wi);
+        if pdf <= 0.0 { return None; }
+
+        *attenuation = Self::spectral(record, self.evaluate_local(&wo, &wi)) * (wi.z() / pdf);
+        material::geometric_side(ray_in, record, Ray::new(record.point, frame.to_world(&wi), ray_in.time()))
+    }
+
+    fn evaluate(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        let frame = record.shading_frame();
+        Self::spectral(record, self.evaluate_local(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized())))
+    }
+
+    fn scattering_pdf(&self, record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let frame = record.shading_frame();
+        self.pdf_local(&frame.to_local(&-direction_in.normalized()), &frame.to_local(&direction_out.normalized()))
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+
+    fn id(&self) -> usize { self.id }
+}
+
+fn rotate(vector: &Vector3D, axis: &Vector3D, angle: f64) -> Vector3D {
+    let (sin, cos) = angle.sin_cos();
+    *vector * cos + *axis * (Vector3D::dot(axis, vector) * (1.0 - cos)) + Vector3D::cross(axis, vector) * sin
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Every entry holds its own flat index, offset by one block per channel.
+    fn indexed() -> MeasuredBrdf { MeasuredBrdf::from_values((0..3 * SAMPLES).map(|index| index as f32).collect()) }
+
+    fn flat_index(theta_half: usize, theta_difference: usize, phi_difference: usize) -> f64 {
+        (phi_difference + PHI_DIFFERENCE_RESOLUTION * (theta_difference + THETA_DIFFERENCE_RESOLUTION * theta_half)) as f64
+    }
+
+    // Directions whose half and difference angles fall in the middle of the given table cell.
+    fn directions(theta_half: usize, theta_difference: usize, phi_difference: usize, phi_half: f64) -> (Vector3D, Vector3D) {
+        let theta_h = ((theta_half as f64 + 0.5) / THETA_HALF_RESOLUTION as f64).powi(2) * PI / 2.0;
+        let theta_d = (theta_difference as f64 + 0.5) / THETA_DIFFERENCE_RESOLUTION as f64 * PI / 2.0;
+        let phi_d = (phi_difference as f64 + 0.5) / PHI_DIFFERENCE_RESOLUTION as f64 * PI;
+
+        let difference = Vector3D::new(theta_d.sin() * phi_d.cos(), theta_d.sin() * phi_d.sin(), theta_d.cos());
+        let wi = rotate(&rotate(&difference, &Vector3D::new(0.0, 1.0, 0.0), theta_h), &Vector3D::new(0.0, 0.0, 1.0), phi_half);
+        let half = Vector3D::new(theta_h.sin() * phi_half.cos(), theta_h.sin() * phi_half.sin(), theta_h.cos());
+
+        (microfacet::reflect(&wi, &half), wi)
+    }
+
+    #[test]
+    fn lookup_reads_each_channel_block() {
+        let brdf = indexed();
+        for (h, d, p) in [(0, 0, 0), (89, 89, 179), (20, 30, 45), (1, 0, 0), (0, 1, 0)] {
+            let expected = flat_index(h, d, p);
+            assert_eq!(brdf.lookup(h, d, p), Color::new(expected, expected + SAMPLES as f64, expected + 2.0 * SAMPLES as f64));
+        }
+    }
+
+    #[test]
+    fn directions_map_to_their_table_cell() {
+        let brdf = indexed();
+        for (h, d, p) in [(20, 30, 45), (5, 60, 120), (60, 10, 3), (0, 45, 90)] {
+            for phi_half in [0.0, 1.0, -2.5] {
+                let (wo, wi) = directions(h, d, p, phi_half);
+                assert_eq!(brdf.evaluate_local(&wo, &wi).x(), flat_index(h, d, p), "cell ({h}, {d}, {p}) at phi_half {phi_half}");
+            }
+        }
+    }
+
+    #[test]
+    fn lookup_is_reciprocal() {
+        let brdf = indexed();
+        let (wo, wi) = directions(20, 30, 45, 0.7);
+        assert_eq!(brdf.evaluate_local(&wo, &wi), brdf.evaluate_local(&wi, &wo));
+    }
+
+    #[test]
+    fn directions_below_the_surface_are_black() {
+        let brdf = indexed();
+        let (wo, wi) = (Vector3D::new(0.0, 0.0, 1.0), Vector3D::new(0.6, 0.0, -0.8));
+        assert!(brdf.evaluate_local(&wo, &wi).near_zero());
+    }
+}
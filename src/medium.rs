@@ -0,0 +1,332 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::aabb::AABB;
+use crate::color::{self, Color};
+use crate::grid::{MajorantGrid, VoxelGrid};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::vector::{Point3D, Vector3D};
+
+// Twice the minimum hit distance the integrators use, so a surface right behind a pass-through point is not skipped.
+const PASS_THROUGH_OFFSET: f64 = 0.002;
+
+pub trait PhaseFunction: Send + Sync {
+    fn evaluate(&self, direction_in: &Vector3D, direction_out: &Vector3D) -> f64;
+
+    fn sample(&self, direction_in: &Vector3D) -> Vector3D;
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Isotropic;
+
+impl PhaseFunction for Isotropic {
+    fn evaluate(&self, _direction_in: &Vector3D, _direction_out: &Vector3D) -> f64 { 1.0 / (4.0 * PI) }
+
+    fn sample(&self, _direction_in: &Vector3D) -> Vector3D { Vector3D::random_normal() }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct HenyeyGreenstein {
+    g: f64,
+}
+
+impl HenyeyGreenstein {
+    pub fn new(g: f64) -> Self { Self { g: g.clamp(-0.99, 0.99) } }
+}
+
+impl PhaseFunction for HenyeyGreenstein {
+    fn evaluate(&self, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        let cos_theta = Vector3D::dot(&direction_in.normalized(), &direction_out.normalized());
+        let denominator = 1.0 + self.g.powi(2) - 2.0 * self.g * cos_theta;
+
+        (1.0 - self.g.powi(2)) / (4.0 * PI * denominator * denominator.sqrt())
+    }
+
+    fn sample(&self, direction_in: &Vector3D) -> Vector3D {
+        let mut rng = rand::thread_rng();
+        let xi = rng.gen_range(0.0..1.0);
+
+        let cos_theta = if self.g.abs() < 1e-3 {
+            1.0 - 2.0 * xi
+        } else {
+            let term = (1.0 - self.g.powi(2)) / (1.0 - self.g + 2.0 * self.g * xi);
+            (1.0 + self.g.powi(2) - term.powi(2)) / (2.0 * self.g)
+        };
+
+        let sin_theta = (1.0 - cos_theta.powi(2)).max(0.0).sqrt();
+        let phi = 2.0 * PI * rng.gen_range(0.0..1.0);
+
+        Onb::new(direction_in).to_world(&Vector3D::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta))
+    }
+}
+
+pub struct MediumInteraction {
+    phase: Arc<dyn PhaseFunction>,
+    weight: Color,
+    emission: Color,
+}
+
+impl MediumInteraction {
+    pub fn new(phase: Arc<dyn PhaseFunction>, weight: Color, emission: Color) -> Self { Self { phase, weight, emission } }
+
+    pub fn record(ray: &Ray, depth: f64, material: Arc<dyn Material>) -> HitRecord {
+        HitRecord { time: ray.time(), medium: true, wavelengths: ray.wavelengths(), ..HitRecord::new(ray.at(depth), Vector3D::default(), Some(material), depth, 0.0, 0.0, true) }
+    }
+}
+
+impl Material for MediumInteraction {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        if self.weight.near_zero() { return None; }
+
+        let direction = self.phase.sample(&ray_in.direction().normalized());
+        *attenuation = self.weight;
+
+        Some(Ray::new(record.point, direction, ray_in.time()))
+    }
+
+    fn emitted(&self, _record: &HitRecord) -> Color { self.emission }
+
+    fn evaluate(&self, _record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> Color {
+        self.weight * self.phase.evaluate(direction_in, direction_out)
+    }
+
+    fn scattering_pdf(&self, _record: &HitRecord, direction_in: &Vector3D, direction_out: &Vector3D) -> f64 {
+        self.phase.evaluate(direction_in, direction_out)
+    }
+
+    fn is_specular(&self, _record: &HitRecord) -> bool { false }
+}
+
+pub struct Transmission {
+    weight: Color,
+}
+
+impl Transmission {
+    pub fn new(weight: Color) -> Self { Self { weight } }
+}
+
+impl Material for Transmission {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        *attenuation = self.weight;
+        Some(Ray::new(record.point, ray_in.direction(), ray_in.time()).with_differential(ray_in.differential()))
+    }
+}
+
+pub struct ConstantMedium {
+    boundary: Arc<dyn Hittable>,
+    sigma_a: Color,
+    sigma_s: Color,
+    phase: Arc<dyn PhaseFunction>,
+}
+
+impl ConstantMedium {
+    pub fn new(boundary: Arc<dyn Hittable>, sigma_a: Color, sigma_s: Color, phase: Arc<dyn PhaseFunction>) -> Self {
+        Self { boundary, sigma_a, sigma_s, phase }
+    }
+
+    pub fn from_density(boundary: Arc<dyn Hittable>, density: f64, albedo: Color, phase: Arc<dyn PhaseFunction>) -> Self {
+        let sigma_s = albedo * density;
+        Self::new(boundary, Color::new(density, density, density) - sigma_s, sigma_s, phase)
+    }
+
+    fn coefficients(&self, ray: &Ray) -> (Color, Color) {
+        match ray.wavelengths() {
+            Some(wavelengths) => {
+                let sigma_s = wavelengths.unbounded(&self.sigma_s);
+                (wavelengths.unbounded(&self.sigma_a) + sigma_s, sigma_s)
+            }
+            None => (self.sigma_a + self.sigma_s, self.sigma_s),
+        }
+    }
+
+    fn overlap(&self, ray: &Ray, interval: &Interval) -> Option<(f64, f64, bool)> {
+        let entry = self.boundary.hit(ray, &mut Interval::universe())?;
+        let exit = self.boundary.hit(ray, &mut Interval::new(entry.depth + 0.0001, f64::INFINITY))?;
+
+        let start = entry.depth.max(interval.min).max(0.0);
+        let end = exit.depth.min(interval.max);
+
+        if start < end { Some((start, end, end < exit.depth)) } else { None }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let (start, end, clipped) = self.overlap(ray, interval)?;
+
+        let ray_length = ray.direction().length();
+        let distance_inside = (end - start) * ray_length;
+
+        let mut rng = rand::thread_rng();
+        let (sigma_t, sigma_s) = self.coefficients(ray);
+        let channel_sigma = sigma_t[rng.gen_range(0usize..3usize)];
+
+        let distance = if channel_sigma > 0.0 { -(1.0 - rng.gen_range(0.0..1.0f64)).ln() / channel_sigma } else { f64::INFINITY };
+
+        if distance < distance_inside {
+            let transmittance = color::transmittance(&sigma_t, distance);
+            let pdf = color::average(&(sigma_t * transmittance));
+
+            let depth = start + distance / ray_length;
+            let material = Arc::new(MediumInteraction::new(self.phase.clone(), sigma_s * transmittance / pdf, Color::default()));
+
+            return Some(MediumInteraction::record(ray, depth, material));
+        }
+
+        let transmittance = color::transmittance(&sigma_t, distance_inside);
+        let weight = transmittance / color::average(&transmittance);
+        if (weight - Color::new(1.0, 1.0, 1.0)).near_zero() { return None; }
+
+        // A surface inside the medium cut the span short; stop just before it so the continuing ray still finds it.
+        let depth = if clipped { end - PASS_THROUGH_OFFSET / ray_length } else { end };
+        if depth <= start { return None; }
+
+        Some(MediumInteraction::record(ray, depth, Arc::new(Transmission::new(weight))))
+    }
+
+    fn bounding_box(&self) -> AABB { self.boundary.bounding_box() }
+
+    fn is_medium(&self) -> bool { true }
+
+    fn transmittance(&self, ray: &Ray, interval: &Interval) -> Color {
+        match self.overlap(ray, interval) {
+            Some((start, end, _)) => color::transmittance(&self.coefficients(ray).0, (end - start) * ray.direction().length()),
+            None => Color::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+pub struct GridMedium {
+    density: Arc<VoxelGrid>,
+    majorants: MajorantGrid,
+    transform: Transform,
+    scale: f64,
+    albedo: Color,
+    phase: Arc<dyn PhaseFunction>,
+    temperature: Option<Arc<VoxelGrid>>,
+    emission_scale: f64,
+}
+
+impl GridMedium {
+    pub fn new(density: Arc<VoxelGrid>, transform: Transform, scale: f64, albedo: Color, phase: Arc<dyn PhaseFunction>) -> Self {
+        let majorants = density.majorants();
+        Self { density, majorants, transform, scale, albedo, phase, temperature: None, emission_scale: 0.0 }
+    }
+
+    pub fn with_emission(mut self, temperature: Arc<VoxelGrid>, emission_scale: f64) -> Self {
+        self.temperature = Some(temperature);
+        self.emission_scale = emission_scale;
+        self
+    }
+
+    fn overlap(ray: &Ray, interval: &Interval) -> Option<(f64, f64)> {
+        let mut start = interval.min.max(0.0);
+        let mut end = interval.max;
+
+        for axis in 0..3 {
+            let inverse = 1.0 / ray.direction()[axis];
+            let near = -ray.origin()[axis] * inverse;
+            let far = (1.0 - ray.origin()[axis]) * inverse;
+
+            start = start.max(near.min(far));
+            end = end.min(near.max(far));
+        }
+
+        if start < end { Some((start, end)) } else { None }
+    }
+
+    fn albedo(&self, ray: &Ray) -> Color {
+        match ray.wavelengths() {
+            Some(wavelengths) => wavelengths.reflectance(&self.albedo),
+            None => self.albedo,
+        }
+    }
+
+    fn emission(&self, ray: &Ray, local: &Point3D) -> Color {
+        let temperature = match &self.temperature {
+            Some(temperature) => temperature.sample(local),
+            None => return Color::default(),
+        };
+
+        let radiance = match ray.wavelengths() {
+            Some(wavelengths) => wavelengths.blackbody(temperature),
+            None => color::blackbody(temperature),
+        };
+
+        (Color::new(1.0, 1.0, 1.0) - self.albedo(ray)) * radiance * self.emission_scale
+    }
+}
+
+impl Hittable for GridMedium {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let local = self.transform.inverse().ray(ray);
+        let (start, end) = Self::overlap(&local, interval)?;
+
+        let mut rng = rand::thread_rng();
+        let stretch = self.scale * ray.direction().length();
+        let mut collision = None;
+
+        self.majorants.traverse(&local.origin(), &local.direction(), start, end, |segment_start, segment_end, majorant| {
+            let sigma_bar = majorant * stretch;
+            if sigma_bar <= 0.0 { return false; }
+
+            let mut depth = segment_start;
+            loop {
+                depth -= (1.0 - rng.gen_range(0.0..1.0f64)).ln() / sigma_bar;
+                if depth >= segment_end { return false; }
+
+                let sigma_t = self.density.sample(&local.at(depth)) * stretch;
+                if rng.gen_range(0.0..1.0) < sigma_t / sigma_bar {
+                    collision = Some(depth);
+                    return true;
+                }
+            }
+        });
+
+        let depth = collision?;
+        let material = Arc::new(MediumInteraction::new(self.phase.clone(), self.albedo(ray), self.emission(ray, &local.at(depth))));
+
+        Some(MediumInteraction::record(ray, depth, material))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        self.transform.bounding_box(&AABB::from_vector_bounds(&Point3D::default(), &Point3D::new(1.0, 1.0, 1.0)))
+    }
+
+    fn is_medium(&self) -> bool { true }
+
+    fn transmittance(&self, ray: &Ray, interval: &Interval) -> Color {
+        let local = self.transform.inverse().ray(ray);
+        let (start, end) = match Self::overlap(&local, interval) {
+            Some(overlap) => overlap,
+            None => return Color::new(1.0, 1.0, 1.0),
+        };
+
+        let mut rng = rand::thread_rng();
+        let stretch = self.scale * ray.direction().length();
+        let mut transmittance = 1.0;
+
+        self.majorants.traverse(&local.origin(), &local.direction(), start, end, |segment_start, segment_end, majorant| {
+            let sigma_bar = majorant * stretch;
+            if sigma_bar <= 0.0 { return false; }
+
+            let mut depth = segment_start;
+            loop {
+                depth -= (1.0 - rng.gen_range(0.0..1.0f64)).ln() / sigma_bar;
+                if depth >= segment_end { return false; }
+
+                transmittance *= 1.0 - (self.density.sample(&local.at(depth)) * stretch / sigma_bar).clamp(0.0, 1.0);
+                if transmittance <= 0.0 { return true; }
+            }
+        });
+
+        Color::new(transmittance, transmittance, transmittance)
+    }
+}
@@ -0,0 +1,264 @@
+use std::f64::consts::PI;
+use std::ops::{Add, Div, Mul, Sub};
+
+use rand::Rng;
+
+use crate::color::Color;
+use crate::vector::Vector3D;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TrowbridgeReitz {
+    alpha_x: f64,
+    alpha_y: f64,
+}
+
+impl TrowbridgeReitz {
+    pub fn new(alpha_x: f64, alpha_y: f64) -> Self { Self { alpha_x: alpha_x.max(1e-4), alpha_y: alpha_y.max(1e-4) } }
+
+    pub fn from_roughness(roughness: f64) -> Self {
+        let alpha = Self::roughness_to_alpha(roughness);
+        Self::new(alpha, alpha)
+    }
+
+    pub fn roughness_to_alpha(roughness: f64) -> f64 { roughness.clamp(0.0, 1.0).powi(2) }
+
+    pub fn effectively_smooth(&self) -> bool { self.alpha_x.max(self.alpha_y) < 1e-3 }
+
+    pub fn d(&self, wm: &Vector3D) -> f64 {
+        let cos2_theta = wm.z().powi(2);
+        let sin2_theta = (1.0 - cos2_theta).max(0.0);
+        if cos2_theta == 0.0 { return 0.0; }
+
+        let tan2_theta = sin2_theta / cos2_theta;
+        let (cos2_phi, sin2_phi) = Self::phi_terms(wm, sin2_theta);
+
+        let e = tan2_theta * (cos2_phi / self.alpha_x.powi(2) + sin2_phi / self.alpha_y.powi(2));
+        1.0 / (PI * self.alpha_x * self.alpha_y * cos2_theta.powi(2) * (1.0 + e).powi(2))
+    }
+
+    pub fn lambda(&self, w: &Vector3D) -> f64 {
+        let cos2_theta = w.z().powi(2);
+        let sin2_theta = (1.0 - cos2_theta).max(0.0);
+        if cos2_theta == 0.0 { return f64::INFINITY; }
+
+        let tan2_theta = sin2_theta / cos2_theta;
+        let (cos2_phi, sin2_phi) = Self::phi_terms(w, sin2_theta);
+
+        let alpha2 = cos2_phi * self.alpha_x.powi(2) + sin2_phi * self.alpha_y.powi(2);
+        ((1.0 + alpha2 * tan2_theta).sqrt() - 1.0) / 2.0
+    }
+
+    pub fn g1(&self, w: &Vector3D) -> f64 { 1.0 / (1.0 + self.lambda(w)) }
+
+    pub fn g(&self, wo: &Vector3D, wi: &Vector3D) -> f64 { 1.0 / (1.0 + self.lambda(wo) + self.lambda(wi)) }
+
+    pub fn pdf(&self, w: &Vector3D, wm: &Vector3D) -> f64 {
+        if w.z() == 0.0 { return 0.0; }
+        self.g1(w) / w.z().abs() * self.d(wm) * Vector3D::dot(w, wm).abs()
+    }
+
+    pub fn sample_wm(&self, w: &Vector3D) -> Vector3D {
+        let mut rng = rand::thread_rng();
+
+        let mut wh = Vector3D::new(self.alpha_x * w.x(), self.alpha_y * w.y(), w.z()).normalized();
+        if wh.z() < 0.0 { wh = -wh; }
+
+        let t1 = if wh.z() < 0.99999 { Vector3D::cross(&Vector3D::new(0.0, 0.0, 1.0), &wh).normalized() } else { Vector3D::new(1.0, 0.0, 0.0) };
+        let t2 = Vector3D::cross(&wh, &t1);
+
+        let radius = rng.gen_range(0.0..1.0f64).sqrt();
+        let angle = 2.0 * PI * rng.gen_range(0.0..1.0);
+        let (px, py) = (radius * angle.cos(), radius * angle.sin());
+
+        let h = (1.0 - px * px).sqrt();
+        let blend = (1.0 + wh.z()) / 2.0;
+        let py = (1.0 - blend) * h + blend * py;
+        let pz = (1.0 - px * px - py * py).max(0.0).sqrt();
+
+        let nh = t1 * px + t2 * py + wh * pz;
+        Vector3D::new(self.alpha_x * nh.x(), self.alpha_y * nh.y(), nh.z().max(1e-6)).normalized()
+    }
+
+    fn phi_terms(w: &Vector3D, sin2_theta: f64) -> (f64, f64) {
+        if sin2_theta <= 0.0 { return (1.0, 0.0); }
+
+        let sin_theta = sin2_theta.sqrt();
+        let cos_phi = (w.x() / sin_theta).clamp(-1.0, 1.0);
+        let sin_phi = (w.y() / sin_theta).clamp(-1.0, 1.0);
+
+        (cos_phi.powi(2), sin_phi.powi(2))
+    }
+}
+
+pub fn reflect(wo: &Vector3D, normal: &Vector3D) -> Vector3D { -*wo + *normal * (2.0 * Vector3D::dot(wo, normal)) }
+
+pub fn refract(wi: &Vector3D, normal: &Vector3D, eta: f64) -> Option<(Vector3D, f64)> {
+    let mut cos_theta_i = Vector3D::dot(normal, wi);
+    let (mut eta, mut normal) = (eta, *normal);
+
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+        normal = -normal;
+    }
+
+    let sin2_theta_t = (1.0 - cos_theta_i.powi(2)).max(0.0) / eta.powi(2);
+    if sin2_theta_t >= 1.0 { return None; }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).sqrt();
+    Some((-*wi / eta + normal * (cos_theta_i / eta - cos_theta_t), eta))
+}
+
+pub fn fresnel_dielectric(cos_theta_i: f64, eta: f64) -> f64 {
+    let mut cos_theta_i = cos_theta_i.clamp(-1.0, 1.0);
+    let mut eta = eta;
+
+    if cos_theta_i < 0.0 {
+        eta = 1.0 / eta;
+        cos_theta_i = -cos_theta_i;
+    }
+
+    let sin2_theta_t = (1.0 - cos_theta_i.powi(2)) / eta.powi(2);
+    if sin2_theta_t >= 1.0 { return 1.0; }
+
+    let cos_theta_t = (1.0 - sin2_theta_t).max(0.0).sqrt();
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+
+    (parallel.powi(2) + perpendicular.powi(2)) / 2.0
+}
+
+pub fn fresnel_conductor(cos_theta_i: f64, eta: &Color, k: &Color) -> Color {
+    Color::new(
+        fresnel_complex(cos_theta_i, Complex::new(eta.x(), k.x())),
+        fresnel_complex(cos_theta_i, Complex::new(eta.y(), k.y())),
+        fresnel_complex(cos_theta_i, Complex::new(eta.z(), k.z())),
+    )
+}
+
+fn fresnel_complex(cos_theta_i: f64, eta: Complex) -> f64 {
+    let cos_theta_i = Complex::new(cos_theta_i.clamp(0.0, 1.0), 0.0);
+    let sin2_theta_i = Complex::new(1.0, 0.0) - cos_theta_i * cos_theta_i;
+    let sin2_theta_t = sin2_theta_i / (eta * eta);
+    let cos_theta_t = (Complex::new(1.0, 0.0) - sin2_theta_t).sqrt();
+
+    let parallel = (eta * cos_theta_i - cos_theta_t) / (eta * cos_theta_i + cos_theta_t);
+    let perpendicular = (cos_theta_i - eta * cos_theta_t) / (cos_theta_i + eta * cos_theta_t);
+
+    (parallel.norm() + perpendicular.norm()) / 2.0
+}
+
+// Airy summation over a film between the outside medium and a possibly absorbing substrate, thickness and wavelength in nm.
+pub fn fresnel_thin_film(cos_theta_i: f64, outside: f64, film: f64, eta: f64, k: f64, thickness: f64, wavelength: f64) -> f64 {
+    let (n0, n1, n2) = (Complex::new(outside, 0.0), Complex::new(film, 0.0), Complex::new(eta, k));
+    let one = Complex::new(1.0, 0.0);
+
+    let cos0 = Complex::new(cos_theta_i.clamp(0.0, 1.0), 0.0);
+    let sin2_0 = one - cos0 * cos0;
+    let cos1 = (one - sin2_0 * (n0 / n1) * (n0 / n1)).sqrt();
+    let cos2 = (one - sin2_0 * (n0 / n2) * (n0 / n2)).sqrt();
+
+    let delta = Complex::new(4.0 * PI * thickness / wavelength, 0.0) * n1 * cos1;
+    let phase = delta.exp_i();
+
+    let airy = |r01: Complex, r12: Complex| ((r01 + r12 * phase) / (one + r01 * r12 * phase)).norm();
+
+    let perpendicular = airy((n0 * cos0 - n1 * cos1) / (n0 * cos0 + n1 * cos1), (n1 * cos1 - n2 * cos2) / (n1 * cos1 + n2 * cos2));
+    let parallel = airy((n1 * cos0 - n0 * cos1) / (n1 * cos0 + n0 * cos1), (n2 * cos1 - n1 * cos2) / (n2 * cos1 + n1 * cos2));
+
+    ((perpendicular + parallel) / 2.0).clamp(0.0, 1.0)
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+struct Complex {
+    re: f64,
+    im: f64,
+}
+
+impl Complex {
+    fn new(re: f64, im: f64) -> Self { Self { re, im } }
+
+    fn norm(&self) -> f64 { self.re * self.re + self.im * self.im }
+
+    fn sqrt(&self) -> Self {
+        let length = self.norm().sqrt();
+        if length == 0.0 { return Self::new(0.0, 0.0); }
+
+        let re = ((length + self.re) / 2.0).max(0.0).sqrt();
+        let im = ((length - self.re) / 2.0).max(0.0).sqrt();
+
+        Self::new(re, if self.im < 0.0 { -im } else { im })
+    }
+
+    // e^(i z)
+    fn exp_i(&self) -> Self {
+        let magnitude = (-self.im).exp();
+        Self::new(magnitude * self.re.cos(), magnitude * self.re.sin())
+    }
+}
+
+impl Add for Complex {
+    type Output = Complex;
+
+    fn add(self, rhs: Self) -> Self::Output { Complex::new(self.re + rhs.re, self.im + rhs.im) }
+}
+
+impl Sub for Complex {
+    type Output = Complex;
+
+    fn sub(self, rhs: Self) -> Self::Output { Complex::new(self.re - rhs.re, self.im - rhs.im) }
+}
+
+impl Mul for Complex {
+    type Output = Complex;
+
+    fn mul(self, rhs: Self) -> Self::Output { Complex::new(self.re * rhs.re - self.im * rhs.im, self.re * rhs.im + self.im * rhs.re) }
+}
+
+impl Div for Complex {
+    type Output = Complex;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        let scale = 1.0 / rhs.norm();
+        Complex::new((self.re * rhs.re + self.im * rhs.im) * scale, (self.im * rhs.re - self.re * rhs.im) * scale)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn vanishing_film_over_a_dielectric_is_bare_fresnel() {
+        for cosine in [1.0, 0.8, 0.5, 0.2] {
+            let film = fresnel_thin_film(cosine, 1.0, 1.33, 1.5, 0.0, 0.0, 550.0);
+            assert!((film - fresnel_dielectric(cosine, 1.5)).abs() < 1e-9, "cosine {cosine}: {film}");
+        }
+    }
+
+    #[test]
+    fn vanishing_film_over_a_conductor_is_bare_fresnel() {
+        let (eta, k) = (Color::new(0.2, 0.9, 1.1), Color::new(3.9, 2.4, 2.2));
+        for cosine in [1.0, 0.7, 0.3] {
+            let bare = fresnel_conductor(cosine, &eta, &k);
+            for channel in 0..3 {
+                let film = fresnel_thin_film(cosine, 1.0, 1.4, eta[channel], k[channel], 0.0, 550.0);
+                assert!((film - bare[channel]).abs() < 1e-9, "cosine {cosine}, channel {channel}: {film} against {}", bare[channel]);
+            }
+        }
+    }
+
+    #[test]
+    fn film_matching_the_outside_index_is_invisible() {
+        let film = fresnel_thin_film(0.9, 1.0, 1.0, 1.5, 0.0, 300.0, 550.0);
+        assert!((film - fresnel_dielectric(0.9, 1.5)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn quarter_wave_film_cancels_normal_reflection() {
+        // A film of index sqrt(1.5) a quarter wavelength thick is the classic antireflection coating.
+        let index = 1.5f64.sqrt();
+        let film = fresnel_thin_film(1.0, 1.0, index, 1.5, 0.0, 550.0 / (4.0 * index), 550.0);
+        assert!(film < 1e-9, "{film}");
+    }
+}
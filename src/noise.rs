@@ -0,0 +1,129 @@
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{Rng, SeedableRng};
+
+use crate::vector::{Point3D, Vector3D};
+
+const POINT_COUNT: usize = 256;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Perlin {
+    gradients: Vec<Vector3D>,
+    permutations: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let gradients = (0..POINT_COUNT).map(|_| loop {
+            let gradient = Vector3D::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0));
+            let length_squared = gradient.length_squared();
+            if length_squared > 1e-6 && length_squared <= 1.0 { break gradient / length_squared.sqrt(); }
+        }).collect();
+
+        let permutations = [0, 1, 2].map(|_| {
+            let mut permutation: Vec<usize> = (0..POINT_COUNT).collect();
+            permutation.shuffle(&mut rng);
+            permutation
+        });
+
+        Self { gradients, permutations }
+    }
+
+    pub fn noise(&self, point: &Point3D) -> f64 {
+        let base = [point.x().floor(), point.y().floor(), point.z().floor()];
+        let fraction = [point.x() - base[0], point.y() - base[1], point.z() - base[2]];
+        let index = base.map(|value| value as i64);
+        let fade = fraction.map(|t| t * t * t * (t * (t * 6.0 - 15.0) + 10.0));
+
+        let mut result = 0.0;
+        for corner in 0..8 {
+            let offset = [corner & 1, (corner >> 1) & 1, (corner >> 2) & 1];
+
+            let hash = self.permutations[0][((index[0] + offset[0] as i64) & 255) as usize]
+                ^ self.permutations[1][((index[1] + offset[1] as i64) & 255) as usize]
+                ^ self.permutations[2][((index[2] + offset[2] as i64) & 255) as usize];
+
+            let weight = Vector3D::new(fraction[0] - offset[0] as f64, fraction[1] - offset[1] as f64, fraction[2] - offset[2] as f64);
+            let blend = (0..3).map(|axis| if offset[axis] == 1 { fade[axis] } else { 1.0 - fade[axis] }).product::<f64>();
+
+            result += blend * Vector3D::dot(&self.gradients[hash], &weight);
+        }
+
+        result
+    }
+
+    pub fn fbm(&self, point: &Point3D, octaves: usize, lacunarity: f64, gain: f64) -> f64 {
+        let mut result = 0.0;
+        let mut amplitude = 1.0;
+        let mut point = *point;
+
+        for _ in 0..octaves {
+            result += amplitude * self.noise(&point);
+            amplitude *= gain;
+            point *= lacunarity;
+        }
+
+        result
+    }
+
+    pub fn turbulence(&self, point: &Point3D, octaves: usize) -> f64 {
+        let mut result = 0.0;
+        let mut amplitude = 1.0;
+        let mut point = *point;
+
+        for _ in 0..octaves {
+            result += amplitude * self.noise(&point).abs();
+            amplitude *= 0.5;
+            point *= 2.0;
+        }
+
+        result
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Worley {
+    seed: u64,
+}
+
+impl Worley {
+    pub fn new(seed: u64) -> Self { Self { seed } }
+
+    pub fn distance(&self, point: &Point3D) -> f64 {
+        let cell = [point.x().floor() as i64, point.y().floor() as i64, point.z().floor() as i64];
+        let mut nearest = f64::INFINITY;
+
+        for dz in -1..=1 {
+            for dy in -1..=1 {
+                for dx in -1..=1 {
+                    let neighbour = [cell[0] + dx, cell[1] + dy, cell[2] + dz];
+                    let feature = self.feature_point(neighbour);
+                    nearest = nearest.min((feature - *point).length());
+                }
+            }
+        }
+
+        nearest
+    }
+
+    fn feature_point(&self, cell: [i64; 3]) -> Point3D {
+        let mut hash = self.seed;
+        for value in cell { hash = Self::mix(hash ^ value as u64); }
+
+        let jitter = [0, 1, 2].map(|axis| {
+            hash = Self::mix(hash.wrapping_add(axis));
+            (hash >> 11) as f64 / (1u64 << 53) as f64
+        });
+
+        Point3D::new(cell[0] as f64 + jitter[0], cell[1] as f64 + jitter[1], cell[2] as f64 + jitter[2])
+    }
+
+    fn mix(value: u64) -> u64 {
+        let mut hash = value.wrapping_add(0x9e3779b97f4a7c15);
+        hash = (hash ^ (hash >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        hash = (hash ^ (hash >> 27)).wrapping_mul(0x94d049bb133111eb);
+        hash ^ (hash >> 31)
+    }
+}
@@ -0,0 +1,37 @@
+use crate::vector::Vector3D;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Onb {
+    axis: [Vector3D; 3],
+}
+
+impl Onb {
+    pub fn new(normal: &Vector3D) -> Self {
+        let w = normal.normalized();
+        let a = if w.x().abs() > 0.9 { Vector3D::new(0.0, 1.0, 0.0) } else { Vector3D::new(1.0, 0.0, 0.0) };
+        let v = Vector3D::cross(&w, &a).normalized();
+        let u = Vector3D::cross(&w, &v);
+
+        Self { axis: [u, v, w] }
+    }
+
+    pub fn from_axes(u: Vector3D, v: Vector3D, w: Vector3D) -> Self { Self { axis: [u, v, w] } }
+
+    pub fn u(&self) -> Vector3D { self.axis[0] }
+
+    pub fn v(&self) -> Vector3D { self.axis[1] }
+
+    pub fn w(&self) -> Vector3D { self.axis[2] }
+
+    pub fn to_world(&self, local: &Vector3D) -> Vector3D {
+        self.axis[0] * local.x() + self.axis[1] * local.y() + self.axis[2] * local.z()
+    }
+
+    pub fn to_local(&self, world: &Vector3D) -> Vector3D {
+        Vector3D::new(
+            Vector3D::dot(world, &self.axis[0]),
+            Vector3D::dot(world, &self.axis[1]),
+            Vector3D::dot(world, &self.axis[2]),
+        )
+    }
+}
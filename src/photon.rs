@@ -0,0 +1,299 @@
+use std::f64::consts::PI;
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::camera::Camera;
+use crate::color::Color;
+use crate::film::Film;
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::integrator::Integrator;
+use crate::interval::Interval;
+use crate::ray::Ray;
+use crate::vector::{Point3D, Vector3D};
+
+const RADIUS_REDUCTION: f64 = 2.0 / 3.0;
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Photon {
+    pub point: Point3D,
+    pub direction: Vector3D,
+    pub power: Color,
+}
+
+#[derive(Default)]
+pub struct PhotonMap {
+    photons: Vec<Photon>,
+    axes: Vec<usize>,
+}
+
+impl PhotonMap {
+    pub fn new(photons: Vec<Photon>) -> Self {
+        let mut map = Self { axes: vec![0; photons.len()], photons };
+        let length = map.photons.len();
+        map.build(0, length);
+
+        map
+    }
+
+    pub fn len(&self) -> usize { self.photons.len() }
+
+    pub fn is_empty(&self) -> bool { self.photons.is_empty() }
+
+    fn build(&mut self, start: usize, end: usize) {
+        if end <= start { return; }
+
+        let mut minimum = Point3D::new(f64::INFINITY, f64::INFINITY, f64::INFINITY);
+        let mut maximum = -minimum;
+
+        for photon in &self.photons[start..end] {
+            for axis in 0..3 {
+                minimum[axis] = minimum[axis].min(photon.point[axis]);
+                maximum[axis] = maximum[axis].max(photon.point[axis]);
+            }
+        }
+
+        let extent = maximum - minimum;
+        let axis = if extent.x() > extent.y() && extent.x() > extent.z() { 0 } else if extent.y() > extent.z() { 1 } else { 2 };
+
+        let middle = (start + end) / 2;
+        self.photons[start..end].select_nth_unstable_by(middle - start, |a, b| a.point[axis].total_cmp(&b.point[axis]));
+        self.axes[middle] = axis;
+
+        self.build(start, middle);
+        self.build(middle + 1, end);
+    }
+
+    pub fn gather<F: FnMut(&Photon)>(&self, point: &Point3D, radius: f64, mut visit: F) {
+        self.gather_range(0, self.photons.len(), point, radius * radius, &mut visit);
+    }
+
+    fn gather_range<F: FnMut(&Photon)>(&self, start: usize, end: usize, point: &Point3D, radius_squared: f64, visit: &mut F) {
+        if end <= start { return; }
+
+        let middle = (start + end) / 2;
+        let photon = &self.photons[middle];
+        if (photon.point - *point).length_squared() <= radius_squared { visit(photon); }
+
+        let axis = self.axes[middle];
+        let offset = point[axis] - photon.point[axis];
+
+        let (near, far) = if offset < 0.0 { ((start, middle), (middle + 1, end)) } else { ((middle + 1, end), (start, middle)) };
+
+        self.gather_range(near.0, near.1, point, radius_squared, visit);
+        if offset * offset <= radius_squared { self.gather_range(far.0, far.1, point, radius_squared, visit); }
+    }
+}
+
+// Photons are emitted from the scene's sampleable emitters, gathered in prepare.
+pub struct PhotonMapper {
+    lights: HittableList,
+    photons_per_pass: usize,
+    max_bounces: usize,
+    radius: f64,
+    map: PhotonMap,
+}
+
+impl PhotonMapper {
+    pub fn new(photons_per_pass: usize, initial_radius: f64) -> Self {
+        Self { lights: HittableList::default(), photons_per_pass, max_bounces: 16, radius: initial_radius, map: PhotonMap::default() }
+    }
+
+    pub fn radius(&self) -> f64 { self.radius }
+
+    fn trace_photon(&self, world: &dyn Hittable, photons: &mut Vec<Photon>) {
+        let mut rng = rand::thread_rng();
+        let time = rng.gen_range(0.0..0.1);
+
+        let record = match self.lights.sample_surface(time) {
+            Some(record) => record,
+            None => return,
+        };
+
+        let emission = match &record.material {
+            Some(material) => material.emitted(&record),
+            None => return,
+        };
+
+        let normal = if rng.gen_range(0.0..1.0) < 0.5 { record.normal } else { -record.normal };
+        let mut direction = normal + Vector3D::random_normal();
+        if direction.near_zero() { direction = normal; }
+        direction = direction.normalized();
+
+        let pdf_direction = Vector3D::dot(&normal, &direction) / (2.0 * PI);
+        if pdf_direction <= 0.0 { return; }
+
+        let mut power = emission * (self.lights.area() * Vector3D::dot(&normal, &direction) / (pdf_direction * self.photons_per_pass as f64));
+        let mut ray = Ray::new(record.point, direction, time);
+
+        for bounce in 0..self.max_bounces {
+            let record = match world.hit(&ray, &mut Interval::new(0.001, f64::INFINITY)) {
+                Some(record) => record,
+                None => break,
+            };
+
+            let material = match &record.material {
+                Some(material) => material.clone(),
+                None => break,
+            };
+
+            power = power * ray.transmittance(&record.point);
+            if !material.is_specular(&record) && !record.medium {
+                photons.push(Photon { point: record.point, direction: ray.direction().normalized(), power });
+            }
+
+            let mut attenuation = Color::default();
+            let scattered = match material.scatter(&ray, &record, &mut attenuation) {
+                Some(scattered) => scattered,
+                None => break,
+            };
+
+            let mut throughput = attenuation * material.adjoint_correction(&record, &scattered.direction());
+
+            if bounce > 2 {
+                let survival = throughput.x().max(throughput.y()).max(throughput.z()).min(1.0);
+                if rng.gen_range(0.0..1.0) >= survival { break; }
+                throughput /= survival;
+            }
+
+            power = power * throughput;
+            ray = scattered.inherit(&ray);
+        }
+    }
+
+    fn estimate(&self, record: &HitRecord, ray: &Ray) -> Color {
+        let material = match &record.material {
+            Some(material) => material,
+            None => return Color::default(),
+        };
+
+        let mut flux = Color::default();
+        let direction_out = -ray.direction().normalized();
+
+        self.map.gather(&record.point, self.radius, |photon| {
+            // The record faces the camera ray, so the photon's adjoint correction is taken from the reverse side.
+            let correction = material.adjoint_correction(record, &-photon.direction);
+            flux += material.evaluate(record, &photon.direction, &direction_out) * photon.power / correction;
+        });
+
+        flux / (PI * self.radius.powi(2))
+    }
+
+    fn environment(ray: &Ray, depth: usize, camera: &Camera, world: &dyn Hittable) -> Color {
+        if depth == 0 { return Color::default(); }
+
+        let record = match world.hit(ray, &mut Interval::new(0.001, f64::INFINITY)) {
+            Some(record) => record,
+            None => return camera.background(ray),
+        };
+
+        let material = match &record.material {
+            Some(material) => material,
+            None => return Color::default(),
+        };
+
+        let emitted = if record.medium { material.emitted(&record) } else { Color::default() };
+
+        let mut attenuation = Color::default();
+        let radiance = match material.scatter(ray, &record, &mut attenuation) {
+            Some(scattered) => emitted + attenuation * Self::environment(&scattered.inherit(ray), depth - 1, camera, world),
+            None => emitted,
+        };
+        radiance * ray.transmittance(&record.point)
+    }
+}
+
+impl Integrator for PhotonMapper {
+    fn ray_color(&self, ray: &Ray, camera: &Camera, world: &dyn Hittable, _film: &Film) -> Color {
+        let mut ray = *ray;
+        let mut beta = Color::new(1.0, 1.0, 1.0);
+        let mut color = Color::default();
+
+        for depth in 0..camera.max_depth {
+            let record = match world.hit(&ray, &mut Interval::new(0.001, f64::INFINITY)) {
+                Some(record) => record,
+                None => return color + beta * camera.background(&ray),
+            };
+
+            let material = match &record.material {
+                Some(material) => material.clone(),
+                None => break,
+            };
+
+            beta = beta * ray.transmittance(&record.point);
+            color += beta * material.emitted(&record);
+
+            let mut attenuation = Color::default();
+            let scattered = material.scatter(&ray, &record, &mut attenuation).map(|scattered| scattered.inherit(&ray));
+
+            if !material.is_specular(&record) && !record.medium {
+                color += beta * self.estimate(&record, &ray);
+
+                if let Some(scattered) = scattered {
+                    color += beta * attenuation * Self::environment(&scattered, camera.max_depth - depth - 1, camera, world);
+                }
+
+                break;
+            }
+
+            match scattered {
+                Some(scattered) => {
+                    beta = beta * attenuation;
+                    ray = scattered;
+                }
+                None => break,
+            }
+        }
+
+        color
+    }
+
+    fn passes(&self, samples_per_pixel: usize) -> usize { samples_per_pixel }
+
+    fn prepare(&mut self, world: &dyn Hittable) {
+        self.lights = HittableList::default();
+        world.collect_lights(&mut self.lights);
+    }
+
+    fn begin_pass(&mut self, world: &dyn Hittable, pass: usize) {
+        if pass > 0 {
+            self.radius *= ((pass as f64 + RADIUS_REDUCTION) / (pass as f64 + 1.0)).sqrt();
+        }
+
+        let photons: Vec<Photon> = (0..self.photons_per_pass).into_par_iter().fold(Vec::new, |mut photons, _| {
+            self.trace_photon(world, &mut photons);
+            photons
+        }).flatten().collect();
+
+        self.map = PhotonMap::new(photons);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn gather_matches_brute_force() {
+        let mut rng = StdRng::seed_from_u64(5);
+        let mut point = || Point3D::new(rng.gen_range(-1.0..1.0), rng.gen_range(-1.0..1.0) * 0.2, rng.gen_range(-1.0..1.0));
+
+        // Flattened along y so the splitting axis varies between levels.
+        let photons: Vec<Photon> = (0..2000).map(|index| Photon { point: point(), direction: Vector3D::new(0.0, 1.0, 0.0), power: Color::new(index as f64, 0.0, 0.0) }).collect();
+        let queries: Vec<Point3D> = (0..200).map(|_| point()).collect();
+        let map = PhotonMap::new(photons.clone());
+        assert_eq!(map.len(), photons.len());
+
+        for (query, radius) in queries.iter().zip([0.0, 0.05, 0.2, 0.7, 3.0].into_iter().cycle()) {
+            let mut found = Vec::new();
+            map.gather(query, radius, |photon| found.push(photon.power.x() as usize));
+            found.sort_unstable();
+
+            let expected: Vec<usize> = photons.iter().filter(|photon| (photon.point - *query).length_squared() <= radius * radius).map(|photon| photon.power.x() as usize).collect();
+            assert_eq!(found, expected, "query {query:?} with radius {radius}");
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::aabb::AABB;
+use crate::hittable::{self, HitRecord, Hittable, HittableList};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::onb::Onb;
+use crate::ray::Ray;
+use crate::vector::{Point3D, Vector3D};
+
+const PADDING: f64 = 0.0001;
+
+// Parallelogram spanned by two edges from a corner, with (u, v) running along the edges.
+#[derive(Clone)]
+pub struct Quad {
+    corner: Point3D,
+    u: Vector3D,
+    v: Vector3D,
+    w: Vector3D,
+    normal: Vector3D,
+    offset: f64,
+    area: f64,
+    material: Arc<dyn Material>,
+    bounding_box: AABB,
+    id: usize,
+}
+
+impl Quad {
+    pub fn new(corner: Point3D, u: Vector3D, v: Vector3D, material: Arc<dyn Material>) -> Self {
+        let n = Vector3D::cross(&u, &v);
+        let normal = n.normalized();
+
+        let diagonal = AABB::from_vector_bounds(&corner, &(corner + u + v));
+        let anti_diagonal = AABB::from_vector_bounds(&(corner + u), &(corner + v));
+
+        Self {
+            corner,
+            u,
+            v,
+            w: n / n.length_squared(),
+            normal,
+            offset: Vector3D::dot(&normal, &corner),
+            area: n.length(),
+            material,
+            bounding_box: AABB::from_aabb_bounds(&diagonal, &anti_diagonal).pad(PADDING),
+            id: hittable::next_object_id(),
+        }
+    }
+}
+
+impl Hittable for Quad {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let denominator = Vector3D::dot(&self.normal, &ray.direction());
+        if denominator.abs() < 1e-8 { return None; }
+
+        let depth = (self.offset - Vector3D::dot(&self.normal, &ray.origin())) / denominator;
+        if !interval.surrounds(depth) { return None; }
+
+        let point = ray.at(depth);
+        let planar = point - self.corner;
+        let alpha = Vector3D::dot(&self.w, &Vector3D::cross(&planar, &self.v));
+        let beta = Vector3D::dot(&self.w, &Vector3D::cross(&self.u, &planar));
+
+        let unit = Interval::new(0.0, 1.0);
+        if !unit.contains(alpha) || !unit.contains(beta) { return None; }
+
+        let mut record = HitRecord { depth, time: ray.time(), point, ..HitRecord::default() };
+
+        record.set_face_normal(ray, &self.normal);
+        (record.u, record.v) = (alpha, beta);
+        (record.dpdu, record.dpdv) = (self.u, self.v);
+        record.object_id = self.id;
+        record.set_material(ray, self.material.clone());
+
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn area(&self) -> f64 { self.area }
+
+    fn is_light(&self) -> bool { self.material.is_emissive() }
+
+    fn sample_surface(&self, time: f64) -> Option<HitRecord> {
+        let mut rng = rand::thread_rng();
+        let (alpha, beta) = (rng.gen_range(0.0..1.0), rng.gen_range(0.0..1.0));
+
+        Some(HitRecord {
+            dpdu: self.u,
+            dpdv: self.v,
+            time,
+            object_id: self.id,
+            ..HitRecord::new(self.corner + self.u * alpha + self.v * beta, self.normal, Some(self.material.clone()), 0.0, alpha, beta, true)
+        })
+    }
+}
+
+// Infinite plane through a point; (u, v) are distances along two tangents so textures tile across it.
+#[derive(Clone)]
+pub struct Plane {
+    point: Point3D,
+    frame: Onb,
+    offset: f64,
+    material: Arc<dyn Material>,
+    bounding_box: AABB,
+    id: usize,
+}
+
+impl Plane {
+    pub fn new(point: Point3D, normal: Vector3D, material: Arc<dyn Material>) -> Self {
+        let normal = normal.normalized();
+
+        // Only an axis aligned plane has a finite extent along its normal.
+        let extent = |axis: usize| {
+            let aligned = (0..3).all(|other| other == axis || normal[other] == 0.0);
+            if aligned { Interval::new(point[axis], point[axis]).expand(PADDING) } else { Interval::universe() }
+        };
+
+        Self {
+            point,
+            frame: Onb::new(&normal),
+            offset: Vector3D::dot(&normal, &point),
+            material,
+            bounding_box: AABB::new(extent(0), extent(1), extent(2)),
+            id: hittable::next_object_id(),
+        }
+    }
+}
+
+impl Hittable for Plane {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let normal = self.frame.w();
+        let denominator = Vector3D::dot(&normal, &ray.direction());
+        if denominator.abs() < 1e-8 { return None; }
+
+        let depth = (self.offset - Vector3D::dot(&normal, &ray.origin())) / denominator;
+        if !interval.surrounds(depth) { return None; }
+
+        let mut record = HitRecord { depth, time: ray.time(), point: ray.at(depth), ..HitRecord::default() };
+
+        let planar = record.point - self.point;
+        record.set_face_normal(ray, &normal);
+        (record.u, record.v) = (Vector3D::dot(&planar, &self.frame.u()), Vector3D::dot(&planar, &self.frame.v()));
+        (record.dpdu, record.dpdv) = (self.frame.u(), self.frame.v());
+        record.object_id = self.id;
+        record.set_material(ray, self.material.clone());
+
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+}
+
+// Flat disk; u runs around the rim and v outwards from the center.
+#[derive(Clone)]
+pub struct Disk {
+    center: Point3D,
+    frame: Onb,
+    radius: f64,
+    material: Arc<dyn Material>,
+    bounding_box: AABB,
+    id: usize,
+}
+
+impl Disk {
+    pub fn new(center: Point3D, normal: Vector3D, radius: f64, material: Arc<dyn Material>) -> Self {
+        let normal = normal.normalized();
+
+        // Half extent of a tilted circle along each axis.
+        let extent = Vector3D::new(
+            radius * (1.0 - normal.x().powi(2)).max(0.0).sqrt(),
+            radius * (1.0 - normal.y().powi(2)).max(0.0).sqrt(),
+            radius * (1.0 - normal.z().powi(2)).max(0.0).sqrt(),
+        );
+
+        Self {
+            center,
+            frame: Onb::new(&normal),
+            radius,
+            material,
+            bounding_box: AABB::from_vector_bounds(&(center - extent), &(center + extent)).pad(PADDING),
+            id: hittable::next_object_id(),
+        }
+    }
+
+    fn record(&self, local: &Vector3D) -> HitRecord {
+        let distance = (local.x().powi(2) + local.y().powi(2)).sqrt();
+        let mut phi = local.y().atan2(local.x());
+        if phi < 0.0 { phi += 2.0 * PI; }
+
+        let (radial, tangent) = (self.frame.to_world(&Vector3D::new(phi.cos(), phi.sin(), 0.0)), self.frame.to_world(&Vector3D::new(-phi.sin(), phi.cos(), 0.0)));
+
+        HitRecord {
+            dpdu: tangent * (2.0 * PI * distance),
+            dpdv: radial * self.radius,
+            object_id: self.id,
+            ..HitRecord::new(self.center + self.frame.to_world(local), self.frame.w(), Some(self.material.clone()), 0.0, phi / (2.0 * PI), distance / self.radius, true)
+        }
+    }
+}
+
+impl Hittable for Disk {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let normal = self.frame.w();
+        let denominator = Vector3D::dot(&normal, &ray.direction());
+        if denominator.abs() < 1e-8 { return None; }
+
+        let depth = Vector3D::dot(&normal, &(self.center - ray.origin())) / denominator;
+        if !interval.surrounds(depth) { return None; }
+
+        let local = self.frame.to_local(&(ray.at(depth) - self.center));
+        let local = Vector3D::new(local.x(), local.y(), 0.0);
+        if local.length_squared() > self.radius.powi(2) { return None; }
+
+        let mut record = self.record(&local);
+        record.depth = depth;
+        record.time = ray.time();
+
+        record.set_face_normal(ray, &normal);
+        record.set_material(ray, self.material.clone());
+
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn area(&self) -> f64 { PI * self.radius.powi(2) }
+
+    fn is_light(&self) -> bool { self.material.is_emissive() }
+
+    fn sample_surface(&self, time: f64) -> Option<HitRecord> {
+        let mut rng = rand::thread_rng();
+        let distance = self.radius * rng.gen_range(0.0..1.0f64).sqrt();
+        let phi = 2.0 * PI * rng.gen_range(0.0..1.0);
+
+        Some(HitRecord { time, ..self.record(&Vector3D::new(distance * phi.cos(), distance * phi.sin(), 0.0)) })
+    }
+}
+
+// Axis aligned box with opposite corners a and b, built from six outward facing quads.
+pub fn cuboid(a: &Point3D, b: &Point3D, material: Arc<dyn Material>) -> HittableList {
+    let mut sides = HittableList::default();
+
+    let min = Point3D::new(a.x().min(b.x()), a.y().min(b.y()), a.z().min(b.z()));
+    let max = Point3D::new(a.x().max(b.x()), a.y().max(b.y()), a.z().max(b.z()));
+
+    let dx = Vector3D::new(max.x() - min.x(), 0.0, 0.0);
+    let dy = Vector3D::new(0.0, max.y() - min.y(), 0.0);
+    let dz = Vector3D::new(0.0, 0.0, max.z() - min.z());
+
+    sides.add_object(Arc::new(Quad::new(Point3D::new(min.x(), min.y(), max.z()), dx, dy, material.clone())));
+    sides.add_object(Arc::new(Quad::new(Point3D::new(max.x(), min.y(), max.z()), -dz, dy, material.clone())));
+    sides.add_object(Arc::new(Quad::new(Point3D::new(max.x(), min.y(), min.z()), -dx, dy, material.clone())));
+    sides.add_object(Arc::new(Quad::new(Point3D::new(min.x(), min.y(), min.z()), dz, dy, material.clone())));
+    sides.add_object(Arc::new(Quad::new(Point3D::new(min.x(), max.y(), max.z()), dx, -dz, material.clone())));
+    sides.add_object(Arc::new(Quad::new(Point3D::new(min.x(), min.y(), min.z()), dx, dz, material)));
+
+    sides
+}
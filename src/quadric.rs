@@ -0,0 +1,576 @@
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::hittable::{self, HitRecord, Hittable, Span};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::transform::Transform;
+use crate::vector::{Point3D, Vector3D};
+
+const PADDING: f64 = 0.0001;
+
+// Surfaces of revolution about the local z axis, swept through phi_max degrees and placed in the scene by a transform.
+
+// A crossing of the local surface, with the outward normal and parametric derivatives still in object space.
+struct LocalHit {
+    depth: f64,
+    normal: Vector3D,
+    u: f64,
+    v: f64,
+    dpdu: Vector3D,
+    dpdv: Vector3D,
+}
+
+#[derive(Clone)]
+struct Placement {
+    transform: Transform,
+    material: Arc<dyn Material>,
+    bounding_box: AABB,
+    id: usize,
+}
+
+impl Placement {
+    fn new(transform: Transform, local_bounds: AABB, material: Arc<dyn Material>) -> Self {
+        Self { transform, material, bounding_box: transform.bounding_box(&local_bounds).pad(PADDING), id: hittable::next_object_id() }
+    }
+
+    fn local_ray(&self, ray: &Ray) -> Ray { self.transform.inverse().ray(ray) }
+
+    fn record(&self, ray: &Ray, hit: &LocalHit) -> HitRecord {
+        let mut record = HitRecord { depth: hit.depth, time: ray.time(), point: ray.at(hit.depth), ..HitRecord::default() };
+
+        record.set_face_normal(ray, &self.transform.normal(&hit.normal).normalized());
+        (record.u, record.v) = (hit.u, hit.v);
+        (record.dpdu, record.dpdv) = (self.transform.vector(&hit.dpdu), self.transform.vector(&hit.dpdv));
+        record.object_id = self.id;
+        record.set_material(ray, self.material.clone());
+
+        record
+    }
+
+    fn closest(&self, ray: &Ray, interval: &Interval, hits: Vec<LocalHit>) -> Option<HitRecord> {
+        hits.iter().find(|hit| interval.surrounds(hit.depth)).map(|hit| self.record(ray, hit))
+    }
+
+    fn spans(&self, ray: &Ray, hits: Vec<LocalHit>) -> Vec<Span> { Span::from_crossings(hits.iter().map(|hit| self.record(ray, hit))) }
+}
+
+#[derive(Clone)]
+pub struct Cylinder {
+    radius: f64,
+    z_min: f64,
+    z_max: f64,
+    phi_max: f64,
+    capped: bool,
+    placement: Placement,
+}
+
+impl Cylinder {
+    pub fn new(transform: Transform, radius: f64, z_min: f64, z_max: f64, phi_max: f64, material: Arc<dyn Material>) -> Self {
+        let (z_min, z_max) = (z_min.min(z_max), z_min.max(z_max));
+        let phi_max = sweep(phi_max);
+        let bounds = sweep_bounds(radius, radius, phi_max, Interval::new(z_min, z_max));
+
+        Self { radius, z_min, z_max, phi_max, capped: false, placement: Placement::new(transform, bounds, material) }
+    }
+
+    // Close both ends with disks, or disk sectors for a partial sweep.
+    pub fn capped(mut self) -> Self {
+        self.capped = true;
+        let bounds = sweep_bounds(0.0, self.radius, self.phi_max, Interval::new(self.z_min, self.z_max));
+        self.placement = Placement::new(self.placement.transform, bounds, self.placement.material.clone());
+        self
+    }
+
+    fn intersections(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let mut hits = Vec::new();
+
+        let a = d.x() * d.x() + d.y() * d.y();
+        let b = 2.0 * (d.x() * o.x() + d.y() * o.y());
+        let c = o.x() * o.x() + o.y() * o.y() - self.radius * self.radius;
+
+        for depth in solve_quadratic(a, b, c) {
+            let p = ray.at(depth);
+            let phi = azimuth(&p);
+            if p.z() < self.z_min || p.z() > self.z_max || phi > self.phi_max { continue; }
+
+            hits.push(LocalHit {
+                depth,
+                normal: Vector3D::new(p.x(), p.y(), 0.0),
+                u: phi / self.phi_max,
+                v: (p.z() - self.z_min) / (self.z_max - self.z_min),
+                dpdu: tangent(&p, self.phi_max),
+                dpdv: Vector3D::new(0.0, 0.0, self.z_max - self.z_min),
+            });
+        }
+
+        if self.capped && d.z() != 0.0 {
+            for (height, sign) in [(self.z_min, -1.0), (self.z_max, 1.0)] {
+                let depth = (height - o.z()) / d.z();
+                let p = ray.at(depth);
+                let distance = (p.x() * p.x() + p.y() * p.y()).sqrt();
+                let phi = azimuth(&p);
+                if distance > self.radius || phi > self.phi_max { continue; }
+
+                let radial = if distance > 0.0 { Vector3D::new(p.x(), p.y(), 0.0) / distance } else { Vector3D::new(1.0, 0.0, 0.0) };
+
+                hits.push(LocalHit {
+                    depth,
+                    normal: Vector3D::new(0.0, 0.0, sign),
+                    u: phi / self.phi_max,
+                    v: distance / self.radius,
+                    dpdu: tangent(&p, self.phi_max),
+                    dpdv: radial * self.radius,
+                });
+            }
+        }
+
+        sorted(hits)
+    }
+}
+
+impl Hittable for Cylinder {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        self.placement.closest(ray, interval, self.intersections(&self.placement.local_ray(ray)))
+    }
+
+    fn bounding_box(&self) -> AABB { self.placement.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> { self.placement.spans(ray, self.intersections(&self.placement.local_ray(ray))) }
+}
+
+// Cone with its base of the given radius on z = 0 and its apex at z = height.
+#[derive(Clone)]
+pub struct Cone {
+    radius: f64,
+    height: f64,
+    phi_max: f64,
+    placement: Placement,
+}
+
+impl Cone {
+    pub fn new(transform: Transform, radius: f64, height: f64, phi_max: f64, material: Arc<dyn Material>) -> Self {
+        let phi_max = sweep(phi_max);
+        let bounds = sweep_bounds(0.0, radius, phi_max, Interval::new(0.0, height));
+
+        Self { radius, height, phi_max, placement: Placement::new(transform, bounds, material) }
+    }
+
+    fn intersections(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let k = (self.radius / self.height).powi(2);
+        let apex = o.z() - self.height;
+
+        let a = d.x() * d.x() + d.y() * d.y() - k * d.z() * d.z();
+        let b = 2.0 * (d.x() * o.x() + d.y() * o.y() - k * d.z() * apex);
+        let c = o.x() * o.x() + o.y() * o.y() - k * apex * apex;
+
+        let mut hits = Vec::new();
+
+        for depth in solve_quadratic(a, b, c) {
+            let p = ray.at(depth);
+            let phi = azimuth(&p);
+            if p.z() < 0.0 || p.z() > self.height || phi > self.phi_max { continue; }
+
+            let v = p.z() / self.height;
+            let slant = if v < 1.0 { Vector3D::new(-p.x() / (1.0 - v), -p.y() / (1.0 - v), self.height) } else { Vector3D::new(0.0, 0.0, self.height) };
+
+            hits.push(LocalHit {
+                depth,
+                normal: Vector3D::new(p.x(), p.y(), k * (self.height - p.z())),
+                u: phi / self.phi_max,
+                v,
+                dpdu: tangent(&p, self.phi_max),
+                dpdv: slant,
+            });
+        }
+
+        sorted(hits)
+    }
+}
+
+impl Hittable for Cone {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        self.placement.closest(ray, interval, self.intersections(&self.placement.local_ray(ray)))
+    }
+
+    fn bounding_box(&self) -> AABB { self.placement.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> { self.placement.spans(ray, self.intersections(&self.placement.local_ray(ray))) }
+}
+
+// Paraboloid z = z_max (x^2 + y^2) / radius^2 opening upwards, clipped to [z_min, z_max].
+#[derive(Clone)]
+pub struct Paraboloid {
+    radius: f64,
+    z_min: f64,
+    z_max: f64,
+    phi_max: f64,
+    placement: Placement,
+}
+
+impl Paraboloid {
+    pub fn new(transform: Transform, radius: f64, z_min: f64, z_max: f64, phi_max: f64, material: Arc<dyn Material>) -> Self {
+        let (z_min, z_max) = (z_min.min(z_max).max(0.0), z_min.max(z_max));
+        let phi_max = sweep(phi_max);
+        let bounds = sweep_bounds(radius * (z_min / z_max).sqrt(), radius, phi_max, Interval::new(z_min, z_max));
+
+        Self { radius, z_min, z_max, phi_max, placement: Placement::new(transform, bounds, material) }
+    }
+
+    fn intersections(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let k = self.z_max / (self.radius * self.radius);
+
+        let a = k * (d.x() * d.x() + d.y() * d.y());
+        let b = 2.0 * k * (d.x() * o.x() + d.y() * o.y()) - d.z();
+        let c = k * (o.x() * o.x() + o.y() * o.y()) - o.z();
+
+        let mut hits = Vec::new();
+
+        for depth in solve_quadratic(a, b, c) {
+            let p = ray.at(depth);
+            let phi = azimuth(&p);
+            if p.z() < self.z_min || p.z() > self.z_max || phi > self.phi_max { continue; }
+
+            let height = self.z_max - self.z_min;
+            let meridian = if p.z() > 0.0 { Vector3D::new(p.x() / (2.0 * p.z()), p.y() / (2.0 * p.z()), 1.0) } else { Vector3D::new(1.0, 0.0, 0.0) };
+
+            hits.push(LocalHit {
+                depth,
+                normal: Vector3D::new(2.0 * k * p.x(), 2.0 * k * p.y(), -1.0),
+                u: phi / self.phi_max,
+                v: (p.z() - self.z_min) / height,
+                dpdu: tangent(&p, self.phi_max),
+                dpdv: meridian * height,
+            });
+        }
+
+        sorted(hits)
+    }
+}
+
+impl Hittable for Paraboloid {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        self.placement.closest(ray, interval, self.intersections(&self.placement.local_ray(ray)))
+    }
+
+    fn bounding_box(&self) -> AABB { self.placement.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> { self.placement.spans(ray, self.intersections(&self.placement.local_ray(ray))) }
+}
+
+// Hyperboloid of one sheet, symmetric about z = 0, narrowing to the waist radius at its middle and widening to the rim radius at z = +-half_height.
+#[derive(Clone)]
+pub struct Hyperboloid {
+    waist: f64,
+    curvature: f64,
+    half_height: f64,
+    phi_max: f64,
+    placement: Placement,
+}
+
+impl Hyperboloid {
+    pub fn new(transform: Transform, waist_radius: f64, rim_radius: f64, half_height: f64, phi_max: f64, material: Arc<dyn Material>) -> Self {
+        let phi_max = sweep(phi_max);
+        let bounds = sweep_bounds(waist_radius.min(rim_radius), waist_radius.max(rim_radius), phi_max, Interval::new(-half_height, half_height));
+
+        Self {
+            waist: waist_radius,
+            curvature: (rim_radius * rim_radius - waist_radius * waist_radius) / (half_height * half_height),
+            half_height,
+            phi_max,
+            placement: Placement::new(transform, bounds, material),
+        }
+    }
+
+    fn intersections(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (o, d) = (ray.origin(), ray.direction());
+        let c = self.curvature;
+
+        let qa = d.x() * d.x() + d.y() * d.y() - c * d.z() * d.z();
+        let qb = 2.0 * (d.x() * o.x() + d.y() * o.y() - c * d.z() * o.z());
+        let qc = o.x() * o.x() + o.y() * o.y() - c * o.z() * o.z() - self.waist * self.waist;
+
+        let mut hits = Vec::new();
+
+        for depth in solve_quadratic(qa, qb, qc) {
+            let p = ray.at(depth);
+            let phi = azimuth(&p);
+            if p.z().abs() > self.half_height || phi > self.phi_max { continue; }
+
+            // Along a meridian the radius changes by c z / r^2 per unit of height.
+            let slope = c * p.z() / (p.x() * p.x() + p.y() * p.y());
+
+            hits.push(LocalHit {
+                depth,
+                normal: Vector3D::new(p.x(), p.y(), -c * p.z()),
+                u: phi / self.phi_max,
+                v: (p.z() + self.half_height) / (2.0 * self.half_height),
+                dpdu: tangent(&p, self.phi_max),
+                dpdv: Vector3D::new(p.x() * slope, p.y() * slope, 1.0) * (2.0 * self.half_height),
+            });
+        }
+
+        sorted(hits)
+    }
+}
+
+impl Hittable for Hyperboloid {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        self.placement.closest(ray, interval, self.intersections(&self.placement.local_ray(ray)))
+    }
+
+    fn bounding_box(&self) -> AABB { self.placement.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> { self.placement.spans(ray, self.intersections(&self.placement.local_ray(ray))) }
+}
+
+// Torus around the z axis; u follows the sweep and v runs once around the tube.
+#[derive(Clone)]
+pub struct Torus {
+    major_radius: f64,
+    minor_radius: f64,
+    phi_max: f64,
+    placement: Placement,
+}
+
+impl Torus {
+    pub fn new(transform: Transform, major_radius: f64, minor_radius: f64, phi_max: f64, material: Arc<dyn Material>) -> Self {
+        let phi_max = sweep(phi_max);
+        let bounds = sweep_bounds(
+            (major_radius - minor_radius).max(0.0),
+            major_radius + minor_radius,
+            phi_max,
+            Interval::new(-minor_radius, minor_radius),
+        );
+
+        Self { major_radius, minor_radius, phi_max, placement: Placement::new(transform, bounds, material) }
+    }
+
+    fn intersections(&self, ray: &Ray) -> Vec<LocalHit> {
+        let (big, small) = (self.major_radius, self.minor_radius);
+        let scale = ray.direction().length();
+        if scale == 0.0 { return Vec::new(); }
+
+        // Solve along a unit direction from the point of closest approach to the center, which keeps the quartic well conditioned.
+        let d = ray.direction() / scale;
+        let shift = -Vector3D::dot(&ray.origin(), &d);
+        let o = ray.origin() + d * shift;
+
+        let e = o.length_squared() + big * big - small * small;
+        let f = Vector3D::dot(&o, &d);
+        let planar_d = d.x() * d.x() + d.y() * d.y();
+        let planar_od = o.x() * d.x() + o.y() * d.y();
+        let planar_o = o.x() * o.x() + o.y() * o.y();
+
+        let coefficients = [
+            e * e - 4.0 * big * big * planar_o,
+            4.0 * f * e - 8.0 * big * big * planar_od,
+            2.0 * e + 4.0 * f * f - 4.0 * big * big * planar_d,
+            4.0 * f,
+        ];
+
+        let mut hits = Vec::new();
+
+        for root in solve_quartic(coefficients) {
+            let depth = (root + shift) / scale;
+            let p = ray.at(depth);
+            let phi = azimuth(&p);
+            if phi > self.phi_max { continue; }
+
+            let distance = (p.x() * p.x() + p.y() * p.y()).sqrt();
+            let mut theta = p.z().atan2(distance - big);
+            if theta < 0.0 { theta += 2.0 * PI; }
+
+            let q = p.length_squared() + big * big - small * small;
+            let (sin_phi, cos_phi) = phi.sin_cos();
+            let (sin_theta, cos_theta) = theta.sin_cos();
+
+            hits.push(LocalHit {
+                depth,
+                normal: Vector3D::new(p.x() * (q - 2.0 * big * big), p.y() * (q - 2.0 * big * big), p.z() * q),
+                u: phi / self.phi_max,
+                v: theta / (2.0 * PI),
+                dpdu: tangent(&p, self.phi_max),
+                dpdv: Vector3D::new(-sin_theta * cos_phi, -sin_theta * sin_phi, cos_theta) * (2.0 * PI * small),
+            });
+        }
+
+        sorted(hits)
+    }
+}
+
+impl Hittable for Torus {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        self.placement.closest(ray, interval, self.intersections(&self.placement.local_ray(ray)))
+    }
+
+    fn bounding_box(&self) -> AABB { self.placement.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> { self.placement.spans(ray, self.intersections(&self.placement.local_ray(ray))) }
+}
+
+fn sweep(degrees: f64) -> f64 { degrees.clamp(0.0, 360.0).to_radians() }
+
+fn azimuth(point: &Point3D) -> f64 {
+    let phi = point.y().atan2(point.x());
+    if phi < 0.0 { phi + 2.0 * PI } else { phi }
+}
+
+fn tangent(point: &Point3D, phi_max: f64) -> Vector3D { Vector3D::new(-phi_max * point.y(), phi_max * point.x(), 0.0) }
+
+fn sorted(mut hits: Vec<LocalHit>) -> Vec<LocalHit> {
+    hits.sort_by(|a, b| a.depth.total_cmp(&b.depth));
+    hits
+}
+
+// Bounds of an annular sector between two radii swept from phi = 0 to phi_max, extruded over the given heights.
+fn sweep_bounds(inner: f64, outer: f64, phi_max: f64, z: Interval) -> AABB {
+    let mut angles = vec![0.0, phi_max];
+    angles.extend((1..4).map(|quarter| quarter as f64 * PI / 2.0).filter(|angle| *angle <= phi_max));
+
+    let (mut x, mut y) = (Interval::empty(), Interval::empty());
+    for angle in angles {
+        let (sin, cos) = f64::sin_cos(angle);
+        for radius in [inner, outer] {
+            x = Interval::from_interval_bounds(&x, &Interval::new(radius * cos, radius * cos));
+            y = Interval::from_interval_bounds(&y, &Interval::new(radius * sin, radius * sin));
+        }
+    }
+
+    AABB::new(x, y, z)
+}
+
+fn solve_quadratic(a: f64, b: f64, c: f64) -> Vec<f64> {
+    if a.abs() < 1e-12 {
+        return if b.abs() < 1e-12 { Vec::new() } else { vec![-c / b] };
+    }
+
+    let discriminant = b * b - 4.0 * a * c;
+    if discriminant < 0.0 { return Vec::new(); }
+
+    // Avoid cancellation by computing the larger root first.
+    let q = -0.5 * (b + discriminant.sqrt().copysign(b));
+    if q == 0.0 { return vec![0.0]; }
+
+    let (t0, t1) = (q / a, c / q);
+    vec![t0.min(t1), t0.max(t1)]
+}
+
+// Real roots of x^3 + b x^2 + c x + d.
+fn solve_cubic(b: f64, c: f64, d: f64) -> Vec<f64> {
+    let q = (b * b - 3.0 * c) / 9.0;
+    let r = (2.0 * b * b * b - 9.0 * b * c + 27.0 * d) / 54.0;
+
+    if r * r < q * q * q {
+        let theta = (r / (q * q * q).sqrt()).clamp(-1.0, 1.0).acos();
+        return (0..3).map(|k| -2.0 * q.sqrt() * ((theta + 2.0 * PI * k as f64) / 3.0).cos() - b / 3.0).collect();
+    }
+
+    let a = -(r.abs() + (r * r - q * q * q).sqrt()).cbrt().copysign(r);
+    let b_term = if a != 0.0 { q / a } else { 0.0 };
+    vec![a + b_term - b / 3.0]
+}
+
+// Real roots of x^4 + c[3] x^3 + c[2] x^2 + c[1] x + c[0] by Ferrari's method, polished with Newton steps.
+fn solve_quartic(coefficients: [f64; 4]) -> Vec<f64> {
+    let [d, c, b, a] = coefficients;
+
+    let p = b - 3.0 * a * a / 8.0;
+    let q = c - a * b / 2.0 + a * a * a / 8.0;
+    let r = d - a * c / 4.0 + a * a * b / 16.0 - 3.0 * a * a * a * a / 256.0;
+
+    let mut roots = Vec::new();
+
+    if q.abs() < 1e-12 {
+        for square in solve_quadratic(1.0, p, r) {
+            if square >= 0.0 { roots.extend([square.sqrt(), -square.sqrt()]); }
+        }
+    } else {
+        let m = solve_cubic(p, p * p / 4.0 - r, -q * q / 8.0).into_iter().fold(f64::NEG_INFINITY, f64::max);
+        if m <= 0.0 { return Vec::new(); }
+
+        let s = (2.0 * m).sqrt();
+        roots.extend(solve_quadratic(1.0, s, p / 2.0 + m - q / (2.0 * s)));
+        roots.extend(solve_quadratic(1.0, -s, p / 2.0 + m + q / (2.0 * s)));
+    }
+
+    let polynomial = |x: f64| (((x + a) * x + b) * x + c) * x + d;
+    let derivative = |x: f64| ((4.0 * x + 3.0 * a) * x + 2.0 * b) * x + c;
+
+    roots
+        .into_iter()
+        .map(|root| {
+            let mut x = root - a / 4.0;
+            for _ in 0..3 {
+                let slope = derivative(x);
+                if slope == 0.0 { break; }
+                x -= polynomial(x) / slope;
+            }
+            x
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::color::Color;
+    use crate::material::Lambertian;
+
+    fn assert_roots(mut roots: Vec<f64>, expected: &[f64]) {
+        roots.sort_by(f64::total_cmp);
+        assert_eq!(roots.len(), expected.len(), "roots {roots:?}, expected {expected:?}");
+        for (root, expected) in roots.iter().zip(expected) {
+            assert!((root - expected).abs() < 1e-9, "roots {roots:?}, expected {expected:?}");
+        }
+    }
+
+    #[test]
+    fn quadratic_roots() {
+        assert_roots(solve_quadratic(1.0, -4.0, 3.0), &[1.0, 3.0]);
+        assert_roots(solve_quadratic(0.0, 2.0, -4.0), &[2.0]);
+        assert_roots(solve_quadratic(1.0, 0.0, 1.0), &[]);
+    }
+
+    #[test]
+    fn cubic_roots() {
+        // (x - 1)(x - 2)(x - 3)
+        assert_roots(solve_cubic(-6.0, 11.0, -6.0), &[1.0, 2.0, 3.0]);
+        // (x - 2)(x^2 + 1)
+        assert_roots(solve_cubic(-2.0, 1.0, -2.0), &[2.0]);
+    }
+
+    #[test]
+    fn quartic_roots() {
+        // (x + 1)(x - 1)(x - 2)(x - 3)
+        assert_roots(solve_quartic([-6.0, 5.0, 5.0, -5.0]), &[-1.0, 1.0, 2.0, 3.0]);
+        // Biquadratic (x^2 - 1)(x^2 - 4)
+        assert_roots(solve_quartic([4.0, 0.0, -5.0, 0.0]), &[-2.0, -1.0, 1.0, 2.0]);
+        // x^4 + 1 has no real roots.
+        assert_roots(solve_quartic([1.0, 0.0, 0.0, 0.0]), &[]);
+    }
+
+    #[test]
+    fn torus_crossings_along_its_plane() {
+        let torus = Torus::new(Transform::default(), 2.0, 0.5, 360.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let ray = Ray::new(Point3D::new(-5.0, 0.0, 0.0), Vector3D::new(1.0, 0.0, 0.0), 0.0);
+
+        let depths: Vec<f64> = torus.intersections(&ray).iter().map(|hit| hit.depth).collect();
+        assert_roots(depths, &[2.5, 3.5, 6.5, 7.5]);
+    }
+
+    #[test]
+    fn torus_crossings_off_axis() {
+        // Parallel to the axis through the middle of the tube, so the ray only clips it where z^2 = r^2.
+        let torus = Torus::new(Transform::default(), 2.0, 0.5, 360.0, Arc::new(Lambertian::new(Color::new(0.5, 0.5, 0.5))));
+        let ray = Ray::new(Point3D::new(2.0, 0.0, -3.0), Vector3D::new(0.0, 0.0, 2.0), 0.0);
+
+        let depths: Vec<f64> = torus.intersections(&ray).iter().map(|hit| hit.depth).collect();
+        assert_roots(depths, &[1.25, 1.75]);
+    }
+}
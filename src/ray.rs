@@ -1,19 +1,58 @@
+use crate::color::{self, Color};
+use crate::spectrum::Wavelengths;
 use crate::vector::{Point3D, Vector3D};
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct RayDifferential {
+    pub rx_origin: Point3D,
+    pub rx_direction: Vector3D,
+    pub ry_origin: Point3D,
+    pub ry_direction: Vector3D,
+}
+
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct Ray {
     origin: Point3D,
     direction: Vector3D,
     time: f64,
+    differential: Option<RayDifferential>,
+    wavelengths: Option<Wavelengths>,
+    absorption: Option<Color>,
 }
 
 impl Ray {
-    pub fn default() -> Self {
-        Self { origin: Point3D::default(), direction: Vector3D::default(), time: 0.0 }
+    pub fn new(origin: Point3D, direction: Vector3D, time: f64) -> Self {
+        Self { origin, direction, time, differential: None, wavelengths: None, absorption: None }
     }
 
-    pub fn new(origin: Point3D, direction: Vector3D, time: f64) -> Self {
-        Self { origin, direction, time }
+    pub fn with_differential(mut self, differential: Option<RayDifferential>) -> Self {
+        self.differential = differential;
+        self
+    }
+
+    pub fn with_wavelengths(mut self, wavelengths: Option<Wavelengths>) -> Self {
+        self.wavelengths = wavelengths;
+        self
+    }
+
+    // Absorption coefficient of the dielectric interior the ray sets off into. Only dielectrics set it; zero marks a clear interior.
+    pub fn with_absorption(mut self, absorption: Option<Color>) -> Self {
+        self.absorption = absorption;
+        self
+    }
+
+    // Carry the parent's wavelengths and interior unless the scattering event already picked its own.
+    pub fn inherit(mut self, parent: &Ray) -> Self {
+        if self.wavelengths.is_none() { self.wavelengths = parent.wavelengths; }
+        if self.absorption.is_none() { self.absorption = parent.absorption; }
+        self
+    }
+
+    // Beer-Lambert transmittance of the interior between the origin and a point along the ray.
+    pub fn transmittance(&self, point: &Point3D) -> Color {
+        let Some(absorption) = self.absorption else { return Color::new(1.0, 1.0, 1.0) };
+        let sigma = self.wavelengths.map_or(absorption, |wavelengths| wavelengths.unbounded(&absorption));
+        color::transmittance(&sigma, (*point - self.origin).length())
     }
 
     pub fn at(&self, depth: f64) -> Point3D { self.origin + self.direction * depth }
@@ -23,4 +62,10 @@ impl Ray {
     pub fn direction(&self) -> Vector3D { self.direction }
 
     pub fn time(&self) -> f64 { self.time }
-}
\ No newline at end of file
+
+    pub fn differential(&self) -> Option<RayDifferential> { self.differential }
+
+    pub fn wavelengths(&self) -> Option<Wavelengths> { self.wavelengths }
+
+    pub fn absorption(&self) -> Option<Color> { self.absorption }
+}
@@ -0,0 +1,347 @@
+use std::sync::Arc;
+
+use crate::aabb::AABB;
+use crate::hittable::{self, HitRecord, Hittable, Span};
+use crate::interval::Interval;
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::vector::{Point3D, Vector3D};
+
+const MAX_STEPS: usize = 1024;
+const BISECTION_STEPS: usize = 32;
+
+// Signed distance to a surface, negative inside. It may underestimate the true distance but must never overestimate it.
+pub trait DistanceFunction: Send + Sync {
+    fn distance(&self, point: &Point3D) -> f64;
+
+    fn bounding_box(&self) -> AABB;
+}
+
+pub struct SdfSphere {
+    radius: f64,
+}
+
+impl SdfSphere {
+    pub fn new(radius: f64) -> Self { Self { radius } }
+}
+
+impl DistanceFunction for SdfSphere {
+    fn distance(&self, point: &Point3D) -> f64 { point.length() - self.radius }
+
+    fn bounding_box(&self) -> AABB { symmetric_box(&Vector3D::new(self.radius, self.radius, self.radius)) }
+}
+
+pub struct SdfBox {
+    half_extent: Vector3D,
+}
+
+impl SdfBox {
+    pub fn new(half_extent: Vector3D) -> Self { Self { half_extent } }
+}
+
+impl DistanceFunction for SdfBox {
+    fn distance(&self, point: &Point3D) -> f64 { box_distance(point, &self.half_extent) }
+
+    fn bounding_box(&self) -> AABB { symmetric_box(&self.half_extent) }
+}
+
+// Box whose edges are rounded off with the given radius, keeping the overall half extent.
+pub struct SdfRoundBox {
+    half_extent: Vector3D,
+    radius: f64,
+}
+
+impl SdfRoundBox {
+    pub fn new(half_extent: Vector3D, radius: f64) -> Self {
+        let radius = radius.clamp(0.0, half_extent.x().min(half_extent.y()).min(half_extent.z()));
+        Self { half_extent, radius }
+    }
+}
+
+impl DistanceFunction for SdfRoundBox {
+    fn distance(&self, point: &Point3D) -> f64 {
+        box_distance(point, &(self.half_extent - Vector3D::new(self.radius, self.radius, self.radius))) - self.radius
+    }
+
+    fn bounding_box(&self) -> AABB { symmetric_box(&self.half_extent) }
+}
+
+// Torus lying in the xz plane around the y axis.
+pub struct SdfTorus {
+    major_radius: f64,
+    minor_radius: f64,
+}
+
+impl SdfTorus {
+    pub fn new(major_radius: f64, minor_radius: f64) -> Self { Self { major_radius, minor_radius } }
+}
+
+impl DistanceFunction for SdfTorus {
+    fn distance(&self, point: &Point3D) -> f64 {
+        let ring = (point.x() * point.x() + point.z() * point.z()).sqrt() - self.major_radius;
+        (ring * ring + point.y() * point.y()).sqrt() - self.minor_radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let outer = self.major_radius + self.minor_radius;
+        symmetric_box(&Vector3D::new(outer, self.minor_radius, outer))
+    }
+}
+
+// Segment from a to b swept by a sphere.
+pub struct SdfCapsule {
+    a: Point3D,
+    b: Point3D,
+    radius: f64,
+}
+
+impl SdfCapsule {
+    pub fn new(a: Point3D, b: Point3D, radius: f64) -> Self { Self { a, b, radius } }
+}
+
+impl DistanceFunction for SdfCapsule {
+    fn distance(&self, point: &Point3D) -> f64 {
+        let (pa, ba) = (*point - self.a, self.b - self.a);
+        let h = if ba.near_zero() { 0.0 } else { (Vector3D::dot(&pa, &ba) / ba.length_squared()).clamp(0.0, 1.0) };
+        (pa - ba * h).length() - self.radius
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let radius = Vector3D::new(self.radius, self.radius, self.radius);
+        AABB::from_aabb_bounds(&AABB::from_vector_bounds(&(self.a - radius), &(self.a + radius)), &AABB::from_vector_bounds(&(self.b - radius), &(self.b + radius)))
+    }
+}
+
+pub struct Translation {
+    distance: Arc<dyn DistanceFunction>,
+    offset: Vector3D,
+}
+
+impl Translation {
+    pub fn new(distance: Arc<dyn DistanceFunction>, offset: Vector3D) -> Self { Self { distance, offset } }
+}
+
+impl DistanceFunction for Translation {
+    fn distance(&self, point: &Point3D) -> f64 { self.distance.distance(&(*point - self.offset)) }
+
+    fn bounding_box(&self) -> AABB {
+        let inner = self.distance.bounding_box();
+        AABB::from_vector_bounds(&(Point3D::new(inner.x.min, inner.y.min, inner.z.min) + self.offset), &(Point3D::new(inner.x.max, inner.y.max, inner.z.max) + self.offset))
+    }
+}
+
+// Polynomial smooth minimum; a smoothness of zero gives the plain union.
+pub struct SmoothUnion {
+    left: Arc<dyn DistanceFunction>,
+    right: Arc<dyn DistanceFunction>,
+    smoothness: f64,
+}
+
+impl SmoothUnion {
+    pub fn new(left: Arc<dyn DistanceFunction>, right: Arc<dyn DistanceFunction>, smoothness: f64) -> Self { Self { left, right, smoothness: smoothness.max(0.0) } }
+}
+
+impl DistanceFunction for SmoothUnion {
+    fn distance(&self, point: &Point3D) -> f64 {
+        let (a, b, k) = (self.left.distance(point), self.right.distance(point), self.smoothness);
+        if k == 0.0 { return a.min(b); }
+
+        let h = (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0);
+        b + (a - b) * h - k * h * (1.0 - h)
+    }
+
+    // The blend bulges out by at most a quarter of the smoothness.
+    fn bounding_box(&self) -> AABB {
+        let inner = AABB::from_aabb_bounds(&self.left.bounding_box(), &self.right.bounding_box());
+        AABB::new(inner.x.expand(self.smoothness / 2.0), inner.y.expand(self.smoothness / 2.0), inner.z.expand(self.smoothness / 2.0))
+    }
+}
+
+// Carve the cut out of the base, blending the seam over the smoothness distance.
+pub struct SmoothSubtraction {
+    base: Arc<dyn DistanceFunction>,
+    cut: Arc<dyn DistanceFunction>,
+    smoothness: f64,
+}
+
+impl SmoothSubtraction {
+    pub fn new(base: Arc<dyn DistanceFunction>, cut: Arc<dyn DistanceFunction>, smoothness: f64) -> Self { Self { base, cut, smoothness: smoothness.max(0.0) } }
+}
+
+impl DistanceFunction for SmoothSubtraction {
+    fn distance(&self, point: &Point3D) -> f64 {
+        let (a, b, k) = (self.base.distance(point), -self.cut.distance(point), self.smoothness);
+        if k == 0.0 { return a.max(b); }
+
+        let h = (0.5 - 0.5 * (b - a) / k).clamp(0.0, 1.0);
+        b + (a - b) * h + k * h * (1.0 - h)
+    }
+
+    fn bounding_box(&self) -> AABB { self.base.bounding_box() }
+}
+
+// Copies of the shape on a grid with the given spacing, limit[axis] cells either side of the origin. The shape should fit inside one cell.
+pub struct Repetition {
+    distance: Arc<dyn DistanceFunction>,
+    spacing: Vector3D,
+    limit: [u32; 3],
+}
+
+impl Repetition {
+    pub fn new(distance: Arc<dyn DistanceFunction>, spacing: Vector3D, limit: [u32; 3]) -> Self { Self { distance, spacing, limit } }
+}
+
+impl DistanceFunction for Repetition {
+    fn distance(&self, point: &Point3D) -> f64 {
+        let cell = |axis: usize| {
+            if self.spacing[axis] <= 0.0 { return 0.0; }
+            let limit = self.limit[axis] as f64;
+            self.spacing[axis] * (point[axis] / self.spacing[axis]).round().clamp(-limit, limit)
+        };
+
+        self.distance.distance(&(*point - Vector3D::new(cell(0), cell(1), cell(2))))
+    }
+
+    fn bounding_box(&self) -> AABB {
+        let inner = self.distance.bounding_box();
+        let reach = |axis: usize| self.spacing[axis].max(0.0) * self.limit[axis] as f64;
+
+        AABB::new(
+            Interval::new(inner.x.min - reach(0), inner.x.max + reach(0)),
+            Interval::new(inner.y.min - reach(1), inner.y.max + reach(1)),
+            Interval::new(inner.z.min - reach(2), inner.z.max + reach(2)),
+        )
+    }
+}
+
+// Rotate each horizontal slice about the y axis by rate radians per unit of height.
+pub struct Twist {
+    distance: Arc<dyn DistanceFunction>,
+    rate: f64,
+    stretch: f64,
+    bounding_box: AABB,
+}
+
+impl Twist {
+    pub fn new(distance: Arc<dyn DistanceFunction>, rate: f64) -> Self {
+        let inner = distance.bounding_box();
+        let reach = [inner.x.min, inner.x.max].iter().flat_map(|x| [inner.z.min, inner.z.max].map(|z| (x * x + z * z).sqrt())).fold(0.0, f64::max);
+        let bounding_box = AABB::new(Interval::new(-reach, reach), inner.y, Interval::new(-reach, reach));
+
+        // Twisting shears space by up to rate times the radius, which peaks at the corners of the square box. Dividing by the
+        // largest singular value of that shear keeps the inner distance a bound.
+        let shear = rate.abs() * reach * 2.0f64.sqrt();
+        let stretch = 0.5 * (shear + (shear * shear + 4.0).sqrt());
+        Self { distance, rate, stretch, bounding_box }
+    }
+}
+
+impl DistanceFunction for Twist {
+    fn distance(&self, point: &Point3D) -> f64 {
+        let (sin, cos) = (self.rate * point.y()).sin_cos();
+        let twisted = Point3D::new(cos * point.x() - sin * point.z(), point.y(), sin * point.x() + cos * point.z());
+
+        self.distance.distance(&twisted) / self.stretch
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+}
+
+// Surface traced through a signed distance field within the field's bounding box.
+pub struct SdfShape {
+    distance: Arc<dyn DistanceFunction>,
+    material: Arc<dyn Material>,
+    bounding_box: AABB,
+    epsilon: f64,
+    id: usize,
+}
+
+impl SdfShape {
+    pub fn new(distance: Arc<dyn DistanceFunction>, material: Arc<dyn Material>) -> Self {
+        let inner = distance.bounding_box();
+        let diagonal = Vector3D::new(inner.x.size(), inner.y.size(), inner.z.size()).length().max(1e-3);
+
+        // Tracing starts where the ray enters the box, which must lie clear of surfaces touching the field's own bounds.
+        let margin = 1e-3 * diagonal;
+        let bounding_box = AABB::new(inner.x.expand(margin), inner.y.expand(margin), inner.z.expand(margin));
+
+        Self { distance, material, bounding_box, epsilon: 1e-6 * diagonal, id: hittable::next_object_id() }
+    }
+
+    // Sphere trace between the bounds, reporting every depth where the field changes sign, or only the first.
+    fn crossings(&self, ray: &Ray, interval: &Interval, first_only: bool) -> Vec<f64> {
+        let mut bounds = *interval;
+        if !self.bounding_box.hit(ray, &mut bounds) { return Vec::new(); }
+
+        let scale = ray.direction().length();
+        let field = |depth: f64| self.distance.distance(&ray.at(depth));
+
+        let mut crossings = Vec::new();
+        let mut depth = bounds.min;
+        let mut value = field(depth);
+
+        for _ in 0..MAX_STEPS {
+            let next = (depth + value.abs().max(self.epsilon) / scale).min(bounds.max);
+            let next_value = field(next);
+
+            if (next_value < 0.0) != (value < 0.0) {
+                crossings.push(self.bisect(&field, depth, next, value));
+                if first_only { break; }
+            }
+
+            if next >= bounds.max { break; }
+            (depth, value) = (next, next_value);
+        }
+
+        crossings
+    }
+
+    fn bisect(&self, field: &impl Fn(f64) -> f64, mut low: f64, mut high: f64, low_value: f64) -> f64 {
+        let inside = low_value < 0.0;
+
+        for _ in 0..BISECTION_STEPS {
+            let middle = 0.5 * (low + high);
+            if (field(middle) < 0.0) == inside { low = middle; } else { high = middle; }
+        }
+
+        high
+    }
+
+    fn normal(&self, point: &Point3D) -> Vector3D {
+        let h = 10.0 * self.epsilon;
+        let axis = |offset: Vector3D| self.distance.distance(&(*point + offset)) - self.distance.distance(&(*point - offset));
+
+        Vector3D::new(axis(Vector3D::new(h, 0.0, 0.0)), axis(Vector3D::new(0.0, h, 0.0)), axis(Vector3D::new(0.0, 0.0, h))).normalized()
+    }
+
+    fn record(&self, ray: &Ray, depth: f64) -> HitRecord {
+        let mut record = HitRecord { depth, time: ray.time(), point: ray.at(depth), ..HitRecord::default() };
+
+        record.set_face_normal(ray, &self.normal(&record.point));
+        record.object_id = self.id;
+        record.set_material(ray, self.material.clone());
+
+        record
+    }
+}
+
+impl Hittable for SdfShape {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let depth = self.crossings(ray, interval, true).into_iter().find(|depth| interval.surrounds(*depth))?;
+        Some(self.record(ray, depth))
+    }
+
+    fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn spans(&self, ray: &Ray) -> Vec<Span> {
+        Span::from_crossings(self.crossings(ray, &Interval::universe(), false).into_iter().map(|depth| self.record(ray, depth)))
+    }
+}
+
+fn box_distance(point: &Point3D, half_extent: &Vector3D) -> f64 {
+    let q = Vector3D::new(point.x().abs() - half_extent.x(), point.y().abs() - half_extent.y(), point.z().abs() - half_extent.z());
+    let outside = Vector3D::new(q.x().max(0.0), q.y().max(0.0), q.z().max(0.0)).length();
+    outside + q.x().max(q.y()).max(q.z()).min(0.0)
+}
+
+fn symmetric_box(half_extent: &Vector3D) -> AABB { AABB::from_vector_bounds(&-*half_extent, half_extent) }
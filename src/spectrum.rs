@@ -0,0 +1,205 @@
+use rand::Rng;
+
+use crate::color::{self, Color};
+
+pub const LAMBDA_MIN: f64 = 360.0;
+pub const LAMBDA_MAX: f64 = 830.0;
+
+const CIE_Y_INTEGRAL: f64 = 106.922_074_506_916_32;
+const EQUAL_ENERGY_WHITE: [f64; 3] = [1.200_268_165_015_651_3, 0.949_698_991_488_175_8, 0.908_295_967_868_092];
+
+const XYZ_TO_SRGB: [[f64; 3]; 3] = [
+    [3.240_454_2, -1.537_138_5, -0.498_531_4],
+    [-0.969_266_0, 1.876_010_8, 0.041_556_0],
+    [0.055_643_4, -0.204_025_9, 1.057_225_2],
+];
+
+// Smits' RGB to reflectance basis, ten bins over 380..720nm.
+const SMITS_START: f64 = 380.0;
+const SMITS_BIN: f64 = 34.0;
+const SMITS_WHITE: [f64; 10] = [1.0000, 1.0000, 0.9999, 0.9993, 0.9992, 0.9998, 1.0000, 1.0000, 1.0000, 1.0000];
+const SMITS_CYAN: [f64; 10] = [0.9710, 0.9426, 1.0007, 1.0007, 1.0007, 1.0007, 0.1564, 0.0000, 0.0000, 0.0000];
+const SMITS_MAGENTA: [f64; 10] = [1.0000, 1.0000, 0.9685, 0.2229, 0.0000, 0.0458, 0.8369, 1.0000, 1.0000, 0.9959];
+const SMITS_YELLOW: [f64; 10] = [0.0001, 0.0000, 0.1088, 0.6651, 1.0000, 1.0000, 0.9996, 0.9586, 0.9685, 0.9840];
+const SMITS_RED: [f64; 10] = [0.1012, 0.0515, 0.0000, 0.0000, 0.0000, 0.0000, 0.8325, 1.0149, 1.0149, 1.0149];
+const SMITS_GREEN: [f64; 10] = [0.0000, 0.0000, 0.0273, 0.7937, 1.0000, 0.9418, 0.1719, 0.0000, 0.0000, 0.0025];
+const SMITS_BLUE: [f64; 10] = [1.0000, 1.0000, 0.8916, 0.3323, 0.0000, 0.0000, 0.0003, 0.0369, 0.0483, 0.0496];
+
+// A hero wavelength and two companions spaced evenly across the visible range, carried in a Color's three channels.
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
+pub struct Wavelengths {
+    lambda: [f64; 3],
+    terminated: bool,
+}
+
+impl Wavelengths {
+    pub fn sample() -> Self {
+        let u = rand::thread_rng().gen_range(0.0..1.0f64);
+        let lambda = [0, 1, 2].map(|j| sample_visible((u + j as f64 / 3.0).fract()));
+
+        Self { lambda, terminated: false }
+    }
+
+    pub fn hero(&self) -> f64 { self.lambda[0] }
+
+    pub fn lambda(&self) -> [f64; 3] { self.lambda }
+
+    pub fn is_terminated(&self) -> bool { self.terminated }
+
+    // Collapse onto the hero wavelength after a wavelength dependent event such as dispersion.
+    pub fn terminated(mut self) -> Self {
+        self.terminated = true;
+        self
+    }
+
+    pub fn reflectance(&self, rgb: &Color) -> Color { self.map(|lambda| smits(rgb, lambda)) }
+
+    pub fn unbounded(&self, rgb: &Color) -> Color { self.map(|lambda| unbounded(rgb, lambda)) }
+
+    pub fn blackbody(&self, temperature: f64) -> Color {
+        if temperature <= 0.0 { return Color::default(); }
+
+        let normalization = color::planck(550e-9, 6500.0);
+        self.map(|lambda| color::planck(lambda * 1e-9, temperature) / normalization)
+    }
+
+    pub fn to_rgb(&self, radiance: &Color) -> Color {
+        let mut xyz = [0.0; 3];
+
+        for j in 0..3 {
+            let pdf = visible_pdf(self.lambda[j]);
+            if pdf <= 0.0 { continue; }
+
+            let cmf = color_matching(self.lambda[j]);
+            for c in 0..3 { xyz[c] += radiance[j] * cmf[c] / pdf; }
+        }
+
+        let xyz = xyz.map(|value| value / (3.0 * CIE_Y_INTEGRAL));
+        let rgb = XYZ_TO_SRGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2]);
+
+        Color::new(rgb[0] / EQUAL_ENERGY_WHITE[0], rgb[1] / EQUAL_ENERGY_WHITE[1], rgb[2] / EQUAL_ENERGY_WHITE[2])
+    }
+
+    fn map(&self, f: impl Fn(f64) -> f64) -> Color { Color::new(f(self.lambda[0]), f(self.lambda[1]), f(self.lambda[2])) }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Dispersion {
+    // Coefficients take wavelengths in micrometres.
+    Cauchy { a: f64, b: f64 },
+    Sellmeier { b: [f64; 3], c: [f64; 3] },
+}
+
+impl Dispersion {
+    pub fn bk7() -> Self {
+        Dispersion::Sellmeier { b: [1.039_612_12, 0.231_792_344, 1.010_469_45], c: [0.006_000_698_67, 0.020_017_914_4, 103.560_653] }
+    }
+
+    pub fn fused_silica() -> Self {
+        Dispersion::Sellmeier { b: [0.696_166_3, 0.407_942_6, 0.897_479_4], c: [0.004_679_148_26, 0.013_512_063_1, 97.934_002_5] }
+    }
+
+    pub fn diamond() -> Self { Dispersion::Sellmeier { b: [0.3306, 4.3356, 0.0], c: [0.175 * 0.175, 0.106 * 0.106, 0.0] } }
+
+    pub fn index(&self, wavelength: f64) -> f64 {
+        let micrometres = wavelength / 1000.0;
+        let squared = micrometres * micrometres;
+
+        match self {
+            Dispersion::Cauchy { a, b } => a + b / squared,
+            Dispersion::Sellmeier { b, c } => (1.0 + (0..3).map(|i| b[i] * squared / (squared - c[i])).sum::<f64>()).sqrt(),
+        }
+    }
+}
+
+// Spectral value of an RGB color that may exceed one, such as an emission or an index of refraction.
+pub fn unbounded(rgb: &Color, lambda: f64) -> f64 {
+    let scale = rgb.x().max(rgb.y()).max(rgb.z());
+    if scale <= 0.0 { return 0.0; }
+
+    smits(&(*rgb / scale), lambda) * scale
+}
+
+// RGB color of a reflectance spectrum under an equal-energy illuminant, integrated in 10nm steps.
+pub fn reflectance_to_rgb(reflectance: impl Fn(f64) -> f64) -> Color {
+    let (mut xyz, mut white) = ([0.0; 3], [0.0; 3]);
+
+    let mut lambda = LAMBDA_MIN;
+    while lambda <= LAMBDA_MAX {
+        let value = reflectance(lambda);
+        let cmf = color_matching(lambda);
+        for c in 0..3 {
+            xyz[c] += value * cmf[c];
+            white[c] += cmf[c];
+        }
+        lambda += 10.0;
+    }
+
+    let to_rgb = |xyz: [f64; 3]| XYZ_TO_SRGB.map(|row| row[0] * xyz[0] + row[1] * xyz[1] + row[2] * xyz[2]);
+    let (rgb, white) = (to_rgb(xyz), to_rgb(white));
+
+    Color::new((rgb[0] / white[0]).max(0.0), (rgb[1] / white[1]).max(0.0), (rgb[2] / white[2]).max(0.0))
+}
+
+fn sample_visible(u: f64) -> f64 { 538.0 - 138.888_889 * (0.856_910_62 - 1.827_501_97 * u).atanh() }
+
+fn visible_pdf(lambda: f64) -> f64 {
+    if !(LAMBDA_MIN..=LAMBDA_MAX).contains(&lambda) { return 0.0; }
+    0.003_939_804_2 / (0.0072 * (lambda - 538.0)).cosh().powi(2)
+}
+
+// Wyman, Sloan and Shirley's multi-lobe fit to the CIE 1931 observer.
+fn color_matching(lambda: f64) -> [f64; 3] {
+    let lobe = |mu: f64, below: f64, above: f64| {
+        let sigma = if lambda < mu { below } else { above };
+        (-0.5 * ((lambda - mu) / sigma).powi(2)).exp()
+    };
+
+    [
+        1.056 * lobe(599.8, 37.9, 31.0) + 0.362 * lobe(442.0, 16.0, 26.7) - 0.065 * lobe(501.1, 20.4, 26.2),
+        0.821 * lobe(568.8, 46.9, 40.5) + 0.286 * lobe(530.9, 16.3, 31.1),
+        1.217 * lobe(437.0, 11.8, 36.0) + 0.681 * lobe(459.0, 26.0, 13.8),
+    ]
+}
+
+fn smits(rgb: &Color, lambda: f64) -> f64 {
+    let bin = (((lambda - SMITS_START) / SMITS_BIN).floor().max(0.0) as usize).min(9);
+    let (r, g, b) = (rgb.x(), rgb.y(), rgb.z());
+
+    if r <= g && r <= b {
+        let rest = if g <= b { (g - r) * SMITS_CYAN[bin] + (b - g) * SMITS_BLUE[bin] } else { (b - r) * SMITS_CYAN[bin] + (g - b) * SMITS_GREEN[bin] };
+        r * SMITS_WHITE[bin] + rest
+    } else if g <= r && g <= b {
+        let rest = if r <= b { (r - g) * SMITS_MAGENTA[bin] + (b - r) * SMITS_BLUE[bin] } else { (b - g) * SMITS_MAGENTA[bin] + (r - b) * SMITS_RED[bin] };
+        g * SMITS_WHITE[bin] + rest
+    } else {
+        let rest = if r <= g { (r - b) * SMITS_YELLOW[bin] + (g - r) * SMITS_GREEN[bin] } else { (g - b) * SMITS_YELLOW[bin] + (r - g) * SMITS_RED[bin] };
+        b * SMITS_WHITE[bin] + rest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bk7_matches_its_catalogue_index() {
+        assert!((Dispersion::bk7().index(587.6) - 1.5168).abs() < 1e-4);
+        assert!(Dispersion::bk7().index(450.0) > Dispersion::bk7().index(650.0));
+    }
+
+    #[test]
+    fn equal_energy_white_maps_to_white() {
+        let count = 4096;
+        let mut sum = Color::default();
+
+        for i in 0..count {
+            let u = (i as f64 + 0.5) / count as f64;
+            let lambda = [0, 1, 2].map(|j| sample_visible((u + j as f64 / 3.0).fract()));
+            sum += Wavelengths { lambda, terminated: false }.to_rgb(&Color::new(1.0, 1.0, 1.0));
+        }
+
+        let white = sum / count as f64;
+        for c in 0..3 { assert!((white[c] - 1.0).abs() < 0.02, "equal-energy white came back as {white:?}"); }
+    }
+}
@@ -1,7 +1,8 @@
+use std::f64::consts::PI;
 use std::sync::Arc;
 
 use crate::aabb::AABB;
-use crate::hittable::{HitRecord, Hittable};
+use crate::hittable::{self, HitRecord, Hittable};
 use crate::interval::Interval;
 use crate::material::Material;
 use crate::ray::Ray;
@@ -15,6 +16,7 @@ pub struct Sphere {
     is_moving: bool,
     material: Arc<dyn Material>,
     bounding_box: AABB,
+    id: usize,
 }
 
 impl Sphere {
@@ -28,6 +30,7 @@ impl Sphere {
             is_moving: false,
             material,
             bounding_box: AABB::from_vector_bounds(&(center - radius_vector), &(center + radius_vector)),
+            id: hittable::next_object_id(),
         }
     }
 
@@ -43,10 +46,31 @@ impl Sphere {
             is_moving: true,
             material,
             bounding_box: AABB::from_aabb_bounds(&box1, &box2),
+            id: hittable::next_object_id(),
         }
     }
 
     pub fn center(&self, time: f64) -> Point3D { self.center + self.center_vector * time }
+
+    fn sphere_uv(point: &Point3D) -> (f64, f64) {
+        let theta = (-point.y()).acos();
+        let phi = (-point.z()).atan2(point.x()) + PI;
+
+        (phi / (2.0 * PI), theta / PI)
+    }
+
+    fn sphere_derivatives(&self, point: &Point3D) -> (Vector3D, Vector3D) {
+        let ring_radius = (point.x().powi(2) + point.z().powi(2)).sqrt();
+        let dpdu = Vector3D::new(point.z(), 0.0, -point.x()) * (2.0 * PI * self.radius);
+
+        let dpdv = if ring_radius > 1e-9 {
+            Vector3D::new(-point.x() * point.y() / ring_radius, ring_radius, -point.y() * point.z() / ring_radius) * (PI * self.radius)
+        } else {
+            Vector3D::new(1.0, 0.0, 0.0) * (PI * self.radius)
+        };
+
+        (dpdu, dpdv)
+    }
 }
 
 unsafe impl Send for Sphere {}
@@ -75,14 +99,41 @@ impl Hittable for Sphere {
 
         let mut record = HitRecord::default();
         record.depth = root;
+        record.time = ray.time();
         record.point = ray.at(record.depth);
 
         let outward_normal = (record.point - center) / self.radius;
         record.set_face_normal(ray, &outward_normal);
-        record.material = Some(self.material.clone());
+        (record.u, record.v) = Self::sphere_uv(&outward_normal);
+        (record.dpdu, record.dpdv) = self.sphere_derivatives(&outward_normal);
+
+        let normal_scale = if record.front_face { 1.0 / self.radius } else { -1.0 / self.radius };
+        (record.dndu, record.dndv) = (record.dpdu * normal_scale, record.dpdv * normal_scale);
+        record.object_id = self.id;
+        record.set_material(ray, self.material.clone());
 
         Some(record)
     }
 
     fn bounding_box(&self) -> AABB { self.bounding_box }
+
+    fn area(&self) -> f64 { 4.0 * PI * self.radius.powi(2) }
+
+    fn is_light(&self) -> bool { self.material.is_emissive() }
+
+    fn sample_surface(&self, time: f64) -> Option<HitRecord> {
+        let center = if self.is_moving { self.center(time) } else { self.center };
+        let outward_normal = Vector3D::random_normal();
+
+        let (u, v) = Self::sphere_uv(&outward_normal);
+        let (dpdu, dpdv) = self.sphere_derivatives(&outward_normal);
+
+        Some(HitRecord {
+            dpdu,
+            dpdv,
+            time,
+            object_id: self.id,
+            ..HitRecord::new(center + outward_normal * self.radius, outward_normal, Some(self.material.clone()), 0.0, u, v, true)
+        })
+    }
 }
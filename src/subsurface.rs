@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use rand::Rng;
+
+use crate::aabb::AABB;
+use crate::color::{self, Color};
+use crate::hittable::{HitRecord, Hittable};
+use crate::interval::Interval;
+use crate::material::{self, Material};
+use crate::medium::PhaseFunction;
+use crate::microfacet;
+use crate::ray::Ray;
+use crate::vector::Vector3D;
+
+const MAX_BOUNCES: usize = 1024;
+
+pub struct Subsurface {
+    boundary: Arc<dyn Hittable>,
+    material: Arc<SubsurfaceMaterial>,
+}
+
+impl Subsurface {
+    // The albedo is the color the surface should appear, the mean free path is measured in scene units per channel.
+    pub fn new(boundary: Arc<dyn Hittable>, albedo: Color, mean_free_path: Color, refractive_index: f64, phase: Arc<dyn PhaseFunction>) -> Self {
+        let sigma_t = Color::new(1.0 / mean_free_path.x().max(1e-6), 1.0 / mean_free_path.y().max(1e-6), 1.0 / mean_free_path.z().max(1e-6));
+        let single_scattering = Color::new(invert_albedo(albedo.x()), invert_albedo(albedo.y()), invert_albedo(albedo.z()));
+
+        let material = Arc::new(SubsurfaceMaterial { boundary: boundary.clone(), sigma_t, single_scattering, refractive_index, phase, id: material::next_material_id() });
+        Self { boundary, material }
+    }
+}
+
+impl Hittable for Subsurface {
+    fn hit(&self, ray: &Ray, interval: &mut Interval) -> Option<HitRecord> {
+        let mut record = self.boundary.hit(ray, interval)?;
+        record.set_material(ray, self.material.clone());
+        Some(record)
+    }
+
+    fn bounding_box(&self) -> AABB { self.boundary.bounding_box() }
+}
+
+// Van de Hulst's relation between the multiple scattering albedo of a semi-infinite slab and its single scattering albedo.
+fn invert_albedo(albedo: f64) -> f64 {
+    let albedo = albedo.clamp(0.0, 1.0);
+    1.0 - (4.09712 + 4.20863 * albedo - (9.59217 + 41.6808 * albedo + 17.7126 * albedo * albedo).sqrt()).powi(2)
+}
+
+struct SubsurfaceMaterial {
+    boundary: Arc<dyn Hittable>,
+    sigma_t: Color,
+    single_scattering: Color,
+    refractive_index: f64,
+    phase: Arc<dyn PhaseFunction>,
+    id: usize,
+}
+
+impl SubsurfaceMaterial {
+    fn coefficients(&self, record: &HitRecord) -> (Color, Color) {
+        match &record.wavelengths {
+            Some(wavelengths) => (wavelengths.unbounded(&self.sigma_t), wavelengths.reflectance(&self.single_scattering)),
+            None => (self.sigma_t, self.single_scattering),
+        }
+    }
+
+    // Follow the refracted ray through the interior until it leaves the boundary again.
+    fn walk(&self, ray_in: &Ray, record: &HitRecord, direction: Vector3D) -> Option<(Ray, Color)> {
+        let mut rng = rand::thread_rng();
+        let (sigma_t, single_scattering) = self.coefficients(record);
+
+        let mut throughput = Color::new(1.0, 1.0, 1.0);
+        let mut point = record.point;
+        let mut direction = direction;
+
+        for _ in 0..MAX_BOUNCES {
+            let ray = Ray::new(point, direction, ray_in.time()).with_wavelengths(ray_in.wavelengths());
+            let exit = self.boundary.hit(&ray, &mut Interval::new(0.001, f64::INFINITY))?;
+
+            let channel_sigma = sigma_t[rng.gen_range(0usize..3usize)];
+            let distance = -(1.0 - rng.gen_range(0.0..1.0f64)).ln() / channel_sigma;
+
+            if distance < exit.depth {
+                let transmittance = color::transmittance(&sigma_t, distance);
+                throughput = throughput * sigma_t * single_scattering * transmittance / color::average(&(sigma_t * transmittance));
+                if throughput.near_zero() { return None; }
+
+                point = ray.at(distance);
+                direction = self.phase.sample(&direction);
+                continue;
+            }
+
+            let transmittance = color::transmittance(&sigma_t, exit.depth);
+            throughput = throughput * transmittance / color::average(&transmittance);
+
+            let cosine = Vector3D::dot(&-direction, &exit.normal);
+            let eta = 1.0 / self.refractive_index;
+            point = exit.point;
+
+            if rng.gen_range(0.0..1.0) < microfacet::fresnel_dielectric(cosine, eta) {
+                direction = microfacet::reflect(&-direction, &exit.normal);
+                continue;
+            }
+
+            let (outgoing, _) = microfacet::refract(&-direction, &exit.normal, eta)?;
+            return Some((Ray::new(point, outgoing, ray_in.time()), throughput));
+        }
+
+        None
+    }
+}
+
+impl Material for SubsurfaceMaterial {
+    fn scatter(&self, ray_in: &Ray, record: &HitRecord, attenuation: &mut Color) -> Option<Ray> {
+        let direction = ray_in.direction().normalized();
+        let cosine = Vector3D::dot(&-direction, &record.normal);
+        let eta = if record.front_face { self.refractive_index } else { 1.0 / self.refractive_index };
+
+        *attenuation = Color::new(1.0, 1.0, 1.0);
+
+        if rand::thread_rng().gen_range(0.0..1.0) < microfacet::fresnel_dielectric(cosine, eta) {
+            return Some(Ray::new(record.point, microfacet::reflect(&-direction, &record.normal), ray_in.time()));
+        }
+
+        let (refracted, _) = microfacet::refract(&-direction, &record.normal, eta)?;
+        if !record.front_face { return Some(Ray::new(record.point, refracted, ray_in.time())); }
+
+        let (scattered, throughput) = self.walk(ray_in, record, refracted)?;
+        *attenuation = throughput;
+
+        Some(scattered)
+    }
+
+    fn id(&self) -> usize { self.id }
+}
@@ -0,0 +1,428 @@
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use image::ImageResult;
+
+use crate::color::{self, Color};
+use crate::noise::{Perlin, Worley};
+use crate::vector::{Point3D, Vector3D};
+
+pub trait Texture: Send + Sync {
+    fn value(&self, u: f64, v: f64, point: &Point3D, time: f64) -> Color;
+
+    fn filtered(&self, u: f64, v: f64, point: &Point3D, time: f64, _footprint: &Footprint) -> Color { self.value(u, v, point, time) }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Footprint {
+    pub dudx: f64,
+    pub dvdx: f64,
+    pub dudy: f64,
+    pub dvdy: f64,
+}
+
+impl Footprint {
+    pub fn width(&self) -> f64 { 2.0 * self.dudx.abs().max(self.dvdx.abs()).max(self.dudy.abs()).max(self.dvdy.abs()) }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct SolidColor {
+    albedo: Color,
+}
+
+impl SolidColor {
+    pub fn new(albedo: Color) -> Self { Self { albedo } }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: f64, _v: f64, _point: &Point3D, _time: f64) -> Color { self.albedo }
+}
+
+#[derive(Clone)]
+pub struct CheckerTexture {
+    inverse_scale: f64,
+    even: Arc<dyn Texture>,
+    odd: Arc<dyn Texture>,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: f64, even: Arc<dyn Texture>, odd: Arc<dyn Texture>) -> Self {
+        Self { inverse_scale: 1.0 / scale, even, odd }
+    }
+
+    pub fn from_colors(scale: f64, even: Color, odd: Color) -> Self {
+        Self::new(scale, Arc::new(SolidColor::new(even)), Arc::new(SolidColor::new(odd)))
+    }
+
+    fn is_even(&self, point: &Point3D) -> bool {
+        let x = (self.inverse_scale * point.x()).floor() as i64;
+        let y = (self.inverse_scale * point.y()).floor() as i64;
+        let z = (self.inverse_scale * point.z()).floor() as i64;
+
+        (x + y + z) % 2 == 0
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: f64, v: f64, point: &Point3D, time: f64) -> Color {
+        if self.is_even(point) { self.even.value(u, v, point, time) } else { self.odd.value(u, v, point, time) }
+    }
+
+    fn filtered(&self, u: f64, v: f64, point: &Point3D, time: f64, footprint: &Footprint) -> Color {
+        if self.is_even(point) { self.even.filtered(u, v, point, time, footprint) } else { self.odd.filtered(u, v, point, time, footprint) }
+    }
+}
+
+#[derive(Clone)]
+pub struct ScaleTexture {
+    texture: Arc<dyn Texture>,
+    scale: Color,
+}
+
+impl ScaleTexture {
+    pub fn new(texture: Arc<dyn Texture>, scale: Color) -> Self { Self { texture, scale } }
+}
+
+impl Texture for ScaleTexture {
+    fn value(&self, u: f64, v: f64, point: &Point3D, time: f64) -> Color { self.texture.value(u, v, point, time) * self.scale }
+
+    fn filtered(&self, u: f64, v: f64, point: &Point3D, time: f64, footprint: &Footprint) -> Color {
+        self.texture.filtered(u, v, point, time, footprint) * self.scale
+    }
+}
+
+// Broadcasts a single channel, e.g. roughness from green and metallic from blue in packed glTF textures.
+#[derive(Clone)]
+pub struct ChannelTexture {
+    texture: Arc<dyn Texture>,
+    channel: usize,
+}
+
+impl ChannelTexture {
+    pub fn new(texture: Arc<dyn Texture>, channel: usize) -> Self { Self { texture, channel: channel.min(2) } }
+}
+
+impl Texture for ChannelTexture {
+    fn value(&self, u: f64, v: f64, point: &Point3D, time: f64) -> Color {
+        let value = self.texture.value(u, v, point, time)[self.channel];
+        Color::new(value, value, value)
+    }
+
+    fn filtered(&self, u: f64, v: f64, point: &Point3D, time: f64, footprint: &Footprint) -> Color {
+        let value = self.texture.filtered(u, v, point, time, footprint)[self.channel];
+        Color::new(value, value, value)
+    }
+}
+
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub enum WrapMode {
+    #[default]
+    Repeat,
+    Clamp,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct MipLevel {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+}
+
+impl MipLevel {
+    fn texel(&self, x: i64, y: i64, wrap: WrapMode) -> Color {
+        let x = Self::wrap_index(x, self.width, wrap);
+        let y = Self::wrap_index(y, self.height, wrap);
+
+        self.pixels[y * self.width + x]
+    }
+
+    fn bilinear(&self, u: f64, v: f64, wrap: WrapMode) -> Color {
+        let x = u * self.width as f64 - 0.5;
+        let y = (1.0 - v) * self.height as f64 - 0.5;
+
+        let (x0, y0) = (x.floor(), y.floor());
+        let (tx, ty) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+
+        let top = self.texel(x0, y0, wrap) * (1.0 - tx) + self.texel(x0 + 1, y0, wrap) * tx;
+        let bottom = self.texel(x0, y0 + 1, wrap) * (1.0 - tx) + self.texel(x0 + 1, y0 + 1, wrap) * tx;
+
+        top * (1.0 - ty) + bottom * ty
+    }
+
+    fn downsample(&self) -> Self {
+        let width = self.width.div_ceil(2).max(1);
+        let height = self.height.div_ceil(2).max(1);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for y in 0..height as i64 {
+            for x in 0..width as i64 {
+                let sum = self.texel(2 * x, 2 * y, WrapMode::Clamp)
+                    + self.texel(2 * x + 1, 2 * y, WrapMode::Clamp)
+                    + self.texel(2 * x, 2 * y + 1, WrapMode::Clamp)
+                    + self.texel(2 * x + 1, 2 * y + 1, WrapMode::Clamp);
+                pixels.push(sum * 0.25);
+            }
+        }
+
+        Self { width, height, pixels }
+    }
+
+    fn wrap_index(index: i64, size: usize, wrap: WrapMode) -> usize {
+        match wrap {
+            WrapMode::Repeat => index.rem_euclid(size as i64) as usize,
+            WrapMode::Clamp => index.clamp(0, size as i64 - 1) as usize,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MipMap {
+    levels: Vec<MipLevel>,
+}
+
+impl MipMap {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> io::Result<Self> {
+        if width.checked_mul(height) != Some(pixels.len()) {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "image pixel count does not match its resolution"));
+        }
+
+        let mut levels = vec![MipLevel { width, height, pixels }];
+
+        while let Some(last) = levels.last() {
+            if last.pixels.is_empty() || (last.width == 1 && last.height == 1) { break; }
+            let next = last.downsample();
+            levels.push(next);
+        }
+
+        Ok(Self { levels })
+    }
+
+    pub fn levels(&self) -> usize { self.levels.len() }
+
+    pub fn width(&self) -> usize { self.levels[0].width }
+
+    pub fn height(&self) -> usize { self.levels[0].height }
+
+    pub fn lookup(&self, u: f64, v: f64, width: f64, wrap: WrapMode) -> Color {
+        let last = self.levels.len() - 1;
+        let level = last as f64 + width.max(1e-8).log2();
+
+        if level <= 0.0 { return self.levels[0].bilinear(u, v, wrap); }
+        if level >= last as f64 { return self.levels[last].texel(0, 0, wrap); }
+
+        let lower = level.floor() as usize;
+        let blend = level - lower as f64;
+
+        self.levels[lower].bilinear(u, v, wrap) * (1.0 - blend) + self.levels[lower + 1].bilinear(u, v, wrap) * blend
+    }
+
+    fn read(path: &Path, decode_gamma: bool) -> ImageResult<Self> {
+        let image = image::open(path)?.into_rgb32f();
+
+        let pixels = image.pixels().map(|pixel| {
+            let color = Color::new(pixel[0] as f64, pixel[1] as f64, pixel[2] as f64);
+            if decode_gamma { Color::new(color::gamma_to_linear(color.x()), color::gamma_to_linear(color.y()), color::gamma_to_linear(color.z())) } else { color }
+        }).collect();
+
+        Ok(Self::new(image.width() as usize, image.height() as usize, pixels)?)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImageTexture {
+    mipmap: Arc<MipMap>,
+    wrap: WrapMode,
+}
+
+impl ImageTexture {
+    pub fn new(width: usize, height: usize, pixels: Vec<Color>) -> io::Result<Self> {
+        Ok(Self::from_mipmap(Arc::new(MipMap::new(width, height, pixels)?)))
+    }
+
+    pub fn from_mipmap(mipmap: Arc<MipMap>) -> Self { Self { mipmap, wrap: WrapMode::default() } }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> ImageResult<Self> {
+        let path = path.as_ref();
+        Ok(Self::from_mipmap(Arc::new(MipMap::read(path, !is_hdr(path))?)))
+    }
+
+    pub fn load_linear<P: AsRef<Path>>(path: P) -> ImageResult<Self> { Ok(Self::from_mipmap(Arc::new(MipMap::read(path.as_ref(), false)?))) }
+
+    pub fn with_wrap(mut self, wrap: WrapMode) -> Self {
+        self.wrap = wrap;
+        self
+    }
+
+    pub fn width(&self) -> usize { self.mipmap.width() }
+
+    pub fn height(&self) -> usize { self.mipmap.height() }
+
+    pub fn texel(&self, x: i64, y: i64) -> Color { self.mipmap.levels[0].texel(x, y, self.wrap) }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: f64, v: f64, _point: &Point3D, _time: f64) -> Color {
+        if self.mipmap.levels[0].pixels.is_empty() { return Color::new(0.0, 1.0, 1.0); }
+        self.mipmap.levels[0].bilinear(u, v, self.wrap)
+    }
+
+    fn filtered(&self, u: f64, v: f64, point: &Point3D, time: f64, footprint: &Footprint) -> Color {
+        if self.mipmap.levels[0].pixels.is_empty() || footprint.width() == 0.0 { return self.value(u, v, point, time); }
+        self.mipmap.lookup(u, v, footprint.width(), self.wrap)
+    }
+}
+
+fn is_hdr(path: &Path) -> bool { path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("hdr")) }
+
+#[derive(Default)]
+pub struct TextureCache {
+    mipmaps: Mutex<HashMap<(PathBuf, bool), Arc<MipMap>>>,
+}
+
+impl TextureCache {
+    pub fn new() -> Self { Self::default() }
+
+    pub fn load<P: AsRef<Path>>(&self, path: P) -> ImageResult<ImageTexture> {
+        let path = path.as_ref();
+        self.fetch(path, !is_hdr(path))
+    }
+
+    pub fn load_linear<P: AsRef<Path>>(&self, path: P) -> ImageResult<ImageTexture> { self.fetch(path.as_ref(), false) }
+
+    fn fetch(&self, path: &Path, decode_gamma: bool) -> ImageResult<ImageTexture> {
+        let key = (path.to_path_buf(), decode_gamma);
+        if let Some(mipmap) = self.mipmaps.lock().unwrap().get(&key) { return Ok(ImageTexture::from_mipmap(mipmap.clone())); }
+
+        let mipmap = Arc::new(MipMap::read(path, decode_gamma)?);
+        self.mipmaps.lock().unwrap().insert(key, mipmap.clone());
+
+        Ok(ImageTexture::from_mipmap(mipmap))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColorRamp {
+    stops: Vec<(f64, Color)>,
+}
+
+impl Default for ColorRamp {
+    fn default() -> Self { Self::new(vec![(0.0, Color::default()), (1.0, Color::new(1.0, 1.0, 1.0))]) }
+}
+
+impl ColorRamp {
+    pub fn new(mut stops: Vec<(f64, Color)>) -> Self {
+        stops.retain(|stop| stop.0.is_finite());
+        stops.sort_by(|a, b| a.0.total_cmp(&b.0));
+        Self { stops }
+    }
+
+    pub fn evaluate(&self, t: f64) -> Color {
+        let (first, last) = match (self.stops.first(), self.stops.last()) {
+            (Some(first), Some(last)) => (first, last),
+            _ => return Color::new(t, t, t),
+        };
+
+        // A single stop is a constant, and a NaN lookup falls back to the start of the ramp.
+        if self.stops.len() < 2 || t.is_nan() || t <= first.0 { return first.1; }
+        if t >= last.0 { return last.1; }
+
+        let next = self.stops.iter().position(|stop| stop.0 > t).unwrap_or(self.stops.len() - 1);
+        let (start, end) = (self.stops[next - 1], self.stops[next]);
+        let blend = (t - start.0) / (end.0 - start.0);
+
+        start.1 * (1.0 - blend) + end.1 * blend
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum NoisePattern {
+    Perlin,
+    Fbm { octaves: usize, lacunarity: f64, gain: f64 },
+    Turbulence { octaves: usize },
+    Marble { octaves: usize },
+    Worley,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct NoiseTexture {
+    perlin: Perlin,
+    worley: Worley,
+    pattern: NoisePattern,
+    scale: f64,
+    offset: Vector3D,
+    ramp: ColorRamp,
+}
+
+impl NoiseTexture {
+    pub fn new(pattern: NoisePattern, seed: u64, scale: f64) -> Self {
+        Self {
+            perlin: Perlin::new(seed),
+            worley: Worley::new(seed),
+            pattern,
+            scale,
+            offset: Vector3D::default(),
+            ramp: ColorRamp::default(),
+        }
+    }
+
+    pub fn with_offset(mut self, offset: Vector3D) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    pub fn with_ramp(mut self, ramp: ColorRamp) -> Self {
+        self.ramp = ramp;
+        self
+    }
+
+    pub fn intensity(&self, point: &Point3D) -> f64 {
+        let point = (*point + self.offset) * self.scale;
+
+        match self.pattern {
+            NoisePattern::Perlin => 0.5 * (1.0 + self.perlin.noise(&point)),
+            NoisePattern::Fbm { octaves, lacunarity, gain } => 0.5 * (1.0 + self.perlin.fbm(&point, octaves, lacunarity, gain)),
+            NoisePattern::Turbulence { octaves } => self.perlin.turbulence(&point, octaves),
+            NoisePattern::Marble { octaves } => 0.5 * (1.0 + (point.z() + 10.0 * self.perlin.turbulence(&point, octaves)).sin()),
+            NoisePattern::Worley => self.worley.distance(&point),
+        }
+    }
+}
+
+impl Texture for NoiseTexture {
+    fn value(&self, _u: f64, _v: f64, point: &Point3D, _time: f64) -> Color { self.ramp.evaluate(self.intensity(point).clamp(0.0, 1.0)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn footprint_selects_the_mip_level() {
+        let pixels: Vec<Color> = (0..32).map(|index| Color::new(index as f64, (index % 5) as f64, 1.0)).collect();
+        let average = pixels.iter().fold(Color::default(), |sum, pixel| sum + *pixel) / 32.0;
+        let texture = ImageTexture::new(8, 4, pixels).unwrap();
+        let origin = Point3D::default();
+
+        for (x, y) in [(0, 0), (3, 1), (7, 3), (5, 2)] {
+            let (u, v) = ((x as f64 + 0.5) / 8.0, 1.0 - (y as f64 + 0.5) / 4.0);
+            let sharp = texture.filtered(u, v, &origin, 0.0, &Footprint::default());
+            assert!((sharp - texture.texel(x, y)).length() < 1e-9, "zero footprint at ({x}, {y}) read {sharp:?}");
+        }
+
+        let full = Footprint { dudx: 0.5, dvdx: 0.0, dudy: 0.0, dvdy: 0.5 };
+        for (u, v) in [(0.1, 0.2), (0.5, 0.5), (0.9, 0.7)] {
+            let blurred = texture.filtered(u, v, &origin, 0.0, &full);
+            assert!((blurred - average).length() < 1e-9, "full footprint at ({u}, {v}) read {blurred:?}, expected {average:?}");
+        }
+    }
+
+    #[test]
+    fn mismatched_pixel_count_is_an_error() {
+        let error = ImageTexture::new(4, 4, vec![Color::default(); 15]).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(ImageTexture::new(usize::MAX, 2, Vec::new()).is_err());
+    }
+}
@@ -0,0 +1,132 @@
+use std::ops::Mul;
+
+use crate::aabb::AABB;
+use crate::ray::Ray;
+use crate::vector::{Point3D, Vector3D};
+
+type Matrix = [[f64; 4]; 4];
+
+const IDENTITY: Matrix = [[1.0, 0.0, 0.0, 0.0], [0.0, 1.0, 0.0, 0.0], [0.0, 0.0, 1.0, 0.0], [0.0, 0.0, 0.0, 1.0]];
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Transform {
+    matrix: Matrix,
+    inverse: Matrix,
+}
+
+impl Default for Transform {
+    fn default() -> Self { Self { matrix: IDENTITY, inverse: IDENTITY } }
+}
+
+impl Transform {
+    pub fn translation(offset: &Vector3D) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+
+        for axis in 0..3 {
+            matrix[axis][3] = offset[axis];
+            inverse[axis][3] = -offset[axis];
+        }
+
+        Self { matrix, inverse }
+    }
+
+    pub fn scaling(scale: &Vector3D) -> Self {
+        let mut matrix = IDENTITY;
+        let mut inverse = IDENTITY;
+
+        for axis in 0..3 {
+            matrix[axis][axis] = scale[axis];
+            inverse[axis][axis] = 1.0 / scale[axis];
+        }
+
+        Self { matrix, inverse }
+    }
+
+    pub fn rotation(axis: &Vector3D, degrees: f64) -> Self {
+        let a = axis.normalized();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+
+        let mut matrix = IDENTITY;
+        matrix[0][0] = a.x() * a.x() + (1.0 - a.x() * a.x()) * cos;
+        matrix[0][1] = a.x() * a.y() * (1.0 - cos) - a.z() * sin;
+        matrix[0][2] = a.x() * a.z() * (1.0 - cos) + a.y() * sin;
+        matrix[1][0] = a.x() * a.y() * (1.0 - cos) + a.z() * sin;
+        matrix[1][1] = a.y() * a.y() + (1.0 - a.y() * a.y()) * cos;
+        matrix[1][2] = a.y() * a.z() * (1.0 - cos) - a.x() * sin;
+        matrix[2][0] = a.x() * a.z() * (1.0 - cos) - a.y() * sin;
+        matrix[2][1] = a.y() * a.z() * (1.0 - cos) + a.x() * sin;
+        matrix[2][2] = a.z() * a.z() + (1.0 - a.z() * a.z()) * cos;
+
+        Self { matrix, inverse: Self::transpose(&matrix) }
+    }
+
+    pub fn inverse(&self) -> Self { Self { matrix: self.inverse, inverse: self.matrix } }
+
+    pub fn then(&self, next: &Self) -> Self {
+        Self { matrix: Self::multiply(&next.matrix, &self.matrix), inverse: Self::multiply(&self.inverse, &next.inverse) }
+    }
+
+    pub fn point(&self, point: &Point3D) -> Point3D { Self::apply(&self.matrix, point, 1.0) }
+
+    pub fn vector(&self, vector: &Vector3D) -> Vector3D { Self::apply(&self.matrix, vector, 0.0) }
+
+    pub fn normal(&self, normal: &Vector3D) -> Vector3D { Self::apply(&Self::transpose(&self.inverse), normal, 0.0) }
+
+    pub fn ray(&self, ray: &Ray) -> Ray { Ray::new(self.point(&ray.origin()), self.vector(&ray.direction()), ray.time()) }
+
+    pub fn bounding_box(&self, bounding_box: &AABB) -> AABB {
+        let mut result = AABB::default();
+
+        for corner in 0..8 {
+            let point = Point3D::new(
+                if corner & 1 == 0 { bounding_box.x.min } else { bounding_box.x.max },
+                if corner & 2 == 0 { bounding_box.y.min } else { bounding_box.y.max },
+                if corner & 4 == 0 { bounding_box.z.min } else { bounding_box.z.max },
+            );
+
+            let transformed = self.point(&point);
+            result = AABB::from_aabb_bounds(&result, &AABB::from_vector_bounds(&transformed, &transformed));
+        }
+
+        result
+    }
+
+    fn apply(matrix: &Matrix, vector: &Vector3D, w: f64) -> Vector3D {
+        Vector3D::new(
+            matrix[0][0] * vector.x() + matrix[0][1] * vector.y() + matrix[0][2] * vector.z() + matrix[0][3] * w,
+            matrix[1][0] * vector.x() + matrix[1][1] * vector.y() + matrix[1][2] * vector.z() + matrix[1][3] * w,
+            matrix[2][0] * vector.x() + matrix[2][1] * vector.y() + matrix[2][2] * vector.z() + matrix[2][3] * w,
+        )
+    }
+
+    fn multiply(lhs: &Matrix, rhs: &Matrix) -> Matrix {
+        let mut result = [[0.0; 4]; 4];
+
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| lhs[i][k] * rhs[k][j]).sum();
+            }
+        }
+
+        result
+    }
+
+    fn transpose(matrix: &Matrix) -> Matrix {
+        let mut result = [[0.0; 4]; 4];
+
+        for (i, row) in result.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = matrix[j][i];
+            }
+        }
+
+        result
+    }
+}
+
+impl Mul for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Self) -> Self::Output { rhs.then(&self) }
+}
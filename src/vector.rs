@@ -4,16 +4,12 @@ use rand::Rng;
 
 pub type Point3D = Vector3D;
 
-#[derive(Debug, Copy, Clone, PartialEq)]
+#[derive(Debug, Default, Copy, Clone, PartialEq)]
 pub struct Vector3D {
     values: [f64; 3],
 }
 
 impl Vector3D {
-    pub fn default() -> Self {
-        Self { values: [0.0, 0.0, 0.0] }
-    }
-
     pub fn new(x: f64, y: f64, z: f64) -> Self {
         Self { values: [x, y, z] }
     }
@@ -56,10 +52,10 @@ impl Vector3D {
     }
 
     pub fn refract(uv: &Self, normal: &Self, eta_i_over_eta_t: f64) -> Self {
-        let cos_theta = Self::dot(&-uv.clone(), normal).min(1.0);
+        let cos_theta = Self::dot(&-*uv, normal).min(1.0);
 
-        let ray_out_perpendicular = (normal.clone() * cos_theta + uv.clone()) * eta_i_over_eta_t;
-        let ray_out_parallel = normal.clone() * -(1.0 - ray_out_perpendicular.length_squared()).abs().sqrt();
+        let ray_out_perpendicular = (*normal * cos_theta + *uv) * eta_i_over_eta_t;
+        let ray_out_parallel = *normal * -(1.0 - ray_out_perpendicular.length_squared()).abs().sqrt();
 
         ray_out_perpendicular + ray_out_parallel
     }
@@ -92,7 +88,7 @@ impl Vector3D {
 
     pub fn random_on_hemisphere(normal: &Self) -> Self {
         let on_unit_sphere = Self::random_normal();
-        return if Self::dot(&on_unit_sphere, normal) > 0.0 { on_unit_sphere } else { -on_unit_sphere };
+        if Self::dot(&on_unit_sphere, normal) > 0.0 { on_unit_sphere } else { -on_unit_sphere }
     }
 
     pub fn random_normal() -> Self {